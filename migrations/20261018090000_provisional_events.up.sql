@@ -0,0 +1,2 @@
+-- provisional events are scheduled ahead of time, and superseded by later real events
+ALTER TABLE events ADD COLUMN provisional BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+-- completed pomodoros, identified by the START of the task they were worked on
+CREATE TABLE pomodoros (
+    start INTEGER PRIMARY KEY NOT NULL REFERENCES events(id) ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+-- changes to the history which can be undone, most recent last
+CREATE TABLE undo (
+    id INTEGER PRIMARY KEY NOT NULL,
+    description TEXT NOT NULL,
+    -- how to revert the change, as JSON
+    revision TEXT NOT NULL
+);
@@ -0,0 +1,8 @@
+SELECT id, evt_type, timestamp, message, provisional
+FROM events
+-- at the same time, a STOP ends one task before a START begins the next; other ties go in the order they were logged
+ORDER BY
+    timestamp ASC,
+    evt_type = (SELECT id FROM evt_type WHERE name = 'START') ASC,
+    id ASC
+;
@@ -0,0 +1,4 @@
+SELECT id, evt_type, timestamp, message, provisional
+FROM events
+WHERE id = ?
+;
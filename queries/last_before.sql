@@ -0,0 +1,10 @@
+SELECT id, evt_type, timestamp, message, provisional
+FROM events
+WHERE timestamp < ?
+-- the reverse of the usual order; see all_events.sql
+ORDER BY
+    timestamp DESC,
+    evt_type = (SELECT id FROM evt_type WHERE name = 'START') DESC,
+    id DESC
+LIMIT 1
+;
@@ -0,0 +1,15 @@
+-- a real event supersedes any provisional events scheduled after it,
+-- unless some other real event intervenes
+DELETE FROM events
+WHERE
+    provisional
+    AND timestamp >= ?1
+    AND NOT EXISTS (
+        SELECT 1
+        FROM events AS intervening
+        WHERE
+            NOT intervening.provisional
+            AND intervening.timestamp > ?1
+            AND intervening.timestamp < events.timestamp
+    )
+;
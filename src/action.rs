@@ -1,114 +1,809 @@
-use std::fmt;
+use std::{fmt, io::Write, net::SocketAddr, path::PathBuf};
 
-use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone as _, Utc};
+use chrono::{
+    DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone as _, Utc, Weekday,
+};
+use serde::{Deserialize, Serialize};
 use sqlx::SqliteConnection;
 
 use crate::{
-    db::{self, EvtType, Id, RetrieveEvent},
-    paths,
+    backup::{self, Backup, RestoreSummary},
+    config::Config,
+    db::{self, Access, Db, EvtType, Id, Overlap, RetrieveEvent},
+    doctor::{self, Anomaly},
+    edit, git, idle, interact,
+    interchange::{self, Format, ImportSummary},
+    notify::{self, Notification, Reminders},
+    paths, picker,
+    state::{self, State},
+    task::Task,
+    tui,
 };
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Event {
     pub timestamp: DateTime<Local>,
     pub message: String,
 }
 
+/// A span of time spent on a single task, with a known start and stop.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Span {
+    pub start: DateTime<Local>,
+    pub stop: DateTime<Local>,
+    pub message: String,
+}
+
+/// Import data from another time tracker.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Import {
+    pub format: Format,
+    pub path: PathBuf,
+    /// Report what would be imported without changing anything.
+    pub dry_run: bool,
+}
+
+/// A range of whole local days.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Period {
+    pub first: NaiveDate,
+    /// The last day of the period, inclusive.
+    pub last: NaiveDate,
+}
+
+impl Period {
+    /// A period of a single day.
+    pub fn day(date: NaiveDate) -> Self {
+        Self {
+            first: date,
+            last: date,
+        }
+    }
+
+    /// The week, Monday to Sunday, containing `date`.
+    pub fn week_of(date: NaiveDate) -> Self {
+        let week = date.week(Weekday::Mon);
+        Self {
+            first: week.first_day(),
+            last: week.last_day(),
+        }
+    }
+
+    /// Every day in this period, in order.
+    pub fn days(self) -> impl Iterator<Item = NaiveDate> {
+        self.first
+            .iter_days()
+            .take_while(move |date| *date <= self.last)
+    }
+
+    /// The instants at which this period starts (inclusive) and ends (exclusive).
+    pub fn bounds(self) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+        let start = midnight_of(self.first)?;
+        let end = midnight_of(self.last)? + Duration::days(1);
+        Ok((start, end))
+    }
+}
+
+/// How to lay out a report.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportStyle {
+    /// A table of tasks with their times.
+    #[default]
+    Text,
+    /// A Markdown list suitable for a standup.
+    Markdown,
+}
+
+/// Report on the tasks worked on during a period.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    pub period: Period,
+    pub style: ReportStyle,
+    /// Local git repositories whose commits are listed under the tasks during which they were made.
+    pub repos: Vec<PathBuf>,
+}
+
+impl From<Period> for Report {
+    fn from(period: Period) -> Self {
+        Self {
+            period,
+            style: ReportStyle::default(),
+            repos: Vec::new(),
+        }
+    }
+}
+
+/// Export data in another time tracker's format.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Export {
+    pub format: Format,
+    /// Export only this period, instead of the whole history.
+    pub period: Option<Period>,
+}
+
+/// Restore a backup made by [`Action::Backup`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Restore {
+    pub path: PathBuf,
+    pub mode: backup::Mode,
+}
+
+/// Integration with git repositories.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Git {
+    /// Install hooks into the current repository.
+    InstallHooks,
+    UninstallHooks,
+    /// Run by the `post-checkout` hook: start a task derived from the newly checked-out branch.
+    PostCheckout {
+        /// Whether a branch was checked out, rather than some files.
+        branch_checkout: bool,
+    },
+}
+
+/// Split a task in two at a point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Split {
+    /// The START of the task.
+    pub id: Id,
+    pub at: DateTime<Local>,
+    /// The message of the second part; without one, both parts keep the task's message.
+    pub message: Option<String>,
+}
+
+/// Merge a task with the task which follows it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Merge {
+    pub first: Id,
+    pub second: Id,
+    /// The message of the merged task; without one, it keeps the first task's message.
+    pub message: Option<String>,
+}
+
+/// Options which affect how an action is executed.
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// How to treat new events which would split or truncate existing tasks.
+    pub overlap: Overlap,
+    pub config: Config,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Action {
     Start(Event),
+    /// Start a task now, picking its message from those used recently.
+    StartInteractive,
     Stop(Event),
-    Report(NaiveDate),
+    Worked(Span),
+    /// Start a task now, and schedule it to stop at the end of the span.
+    Timebox(Span),
+    /// Work on a task for a pomodoro, then take a break.
+    Pomodoro(String),
+    Status,
+    Doctor,
+    /// Insert the STOPs implied by the workday limits for every runaway task.
+    AutoStop,
+    /// Watch for idle time until interrupted, asking what to do with it on return.
+    Watch,
+    /// Review and correct tasks on an interactive timeline.
+    Tui,
+    Import(Import),
+    /// Write tasks in another time tracker's format.
+    Export(Export),
+    /// Write a lossless JSON backup of the database to the given file, or to the output.
+    Backup(Option<PathBuf>),
+    Restore(Restore),
+    Git(Git),
+    /// Print the task in progress for a shell prompt, with an optional format overriding the configured one.
+    Prompt(Option<String>),
+    /// Serve actions to other processes until interrupted.
+    Daemon,
+    /// Serve the REST API on this address until interrupted.
+    Serve(SocketAddr),
+    Report(Report),
     PathDatabase,
     PathConfig,
     EventsList(NaiveDate),
     EventRm(Id),
+    TaskSplit(Split),
+    TaskMerge(Merge),
+    /// Undo the most recent split or merge.
+    TaskUndo,
+    Help(String),
 }
 
 impl Action {
-    pub async fn execute(self, conn: &mut SqliteConnection) -> Result<(), Error> {
+    /// Whether this action may change the database.
+    pub fn mutates(&self) -> bool {
+        match self {
+            Self::Start(_)
+            | Self::StartInteractive
+            | Self::Stop(_)
+            | Self::Worked(_)
+            | Self::Timebox(_)
+            | Self::Pomodoro(_)
+            | Self::Doctor
+            | Self::AutoStop
+            | Self::Watch
+            | Self::Tui
+            | Self::Import(_)
+            | Self::Restore(_)
+            | Self::EventRm(_)
+            | Self::TaskSplit(_)
+            | Self::TaskMerge(_)
+            | Self::TaskUndo => true,
+            Self::Git(git) => matches!(git, Git::PostCheckout { .. }),
+            Self::Status
+            | Self::Export(_)
+            | Self::Backup(_)
+            | Self::Prompt(_)
+            | Self::Daemon
+            | Self::Serve(_)
+            | Self::Report(_)
+            | Self::PathDatabase
+            | Self::PathConfig
+            | Self::EventsList(_)
+            | Self::Help(_) => false,
+        }
+    }
+
+    /// Whether the daemon can execute this action on a client's behalf.
+    ///
+    /// Actions which interact with the user, depend on the client's working directory, or don't need the
+    /// database at all are executed locally.
+    pub fn delegable(&self) -> bool {
+        match self {
+            Self::Start(_)
+            | Self::Stop(_)
+            | Self::Worked(_)
+            | Self::Timebox(_)
+            | Self::Status
+            | Self::AutoStop
+            | Self::Export(_)
+            | Self::EventsList(_)
+            | Self::EventRm(_)
+            | Self::TaskSplit(_)
+            | Self::TaskMerge(_)
+            | Self::TaskUndo => true,
+            Self::Report(report) => report.repos.is_empty(),
+            Self::StartInteractive
+            | Self::Doctor
+            | Self::Pomodoro(_)
+            | Self::Watch
+            | Self::Tui
+            | Self::Import(_)
+            | Self::Backup(_)
+            | Self::Restore(_)
+            | Self::Git(_)
+            | Self::Prompt(_)
+            | Self::Daemon
+            | Self::Serve(_)
+            | Self::PathDatabase
+            | Self::PathConfig
+            | Self::Help(_) => false,
+        }
+    }
+
+    /// How much access to the database this action needs, if it needs it at all.
+    pub fn access(&self) -> Access {
+        if self.mutates() {
+            Access::ReadWrite
+        } else {
+            Access::ReadOnly
+        }
+    }
+
+    /// Execute this action, writing its output to `out`.
+    ///
+    /// If `refresh_state` is set and the action changes the database, the state file is rewritten to match.
+    pub async fn execute(
+        self,
+        db: &mut Db,
+        options: &Options,
+        refresh_state: bool,
+        out: &mut dyn Write,
+    ) -> Result<(), Error> {
+        let mutates = self.mutates();
+        self.execute_inner(db, options, out).await?;
+        // keep the cached state in step with the database
+        if mutates && refresh_state {
+            State::refresh(db.conn().await?).await?;
+        }
+        Ok(())
+    }
+
+    async fn execute_inner(
+        self,
+        db: &mut Db,
+        options: &Options,
+        out: &mut dyn Write,
+    ) -> Result<(), Error> {
         match self {
             Self::PathDatabase => {
                 let path = paths::database();
                 let path = path.display();
-                println!("{path}");
+                writeln!(out, "{path}")?;
                 Ok(())
             }
             Self::PathConfig => {
                 let path = paths::config();
                 let path = path.display();
-                println!("{path}");
+                writeln!(out, "{path}")?;
+                Ok(())
+            }
+            Self::Help(text) => {
+                write!(out, "{text}")?;
                 Ok(())
             }
-            Self::Start(evt) => handle_start_stop(conn, db::EvtType::Start, evt).await,
-            Self::Stop(evt) => handle_start_stop(conn, db::EvtType::Stop, evt).await,
-            Self::Report(date) => handle_report(conn, date).await,
-            Self::EventsList(date) => handle_events_list(conn, date).await,
-            Self::EventRm(id) => handle_event_rm(conn, id).await,
+            Self::Start(evt) => {
+                handle_start_stop(db.conn().await?, options, EvtType::Start, evt, out).await
+            }
+            Self::StartInteractive => {
+                handle_start_interactive(db.conn().await?, options, out).await
+            }
+            Self::Stop(evt) => {
+                handle_start_stop(db.conn().await?, options, EvtType::Stop, evt, out).await
+            }
+            Self::Worked(span) => handle_worked(db.conn().await?, options, span, out).await,
+            Self::Timebox(span) => handle_timebox(db.conn().await?, options, span, out)
+                .await
+                .map(|_| ()),
+            Self::Pomodoro(message) => {
+                handle_pomodoro(db.conn().await?, options, message, out).await
+            }
+            Self::Status => handle_status(db.conn().await?, out).await,
+            Self::Doctor => handle_doctor(db.conn().await?, options, out).await,
+            Self::AutoStop => handle_autostop(db.conn().await?, options, out).await,
+            Self::Watch => handle_watch(db.conn().await?, options, out).await,
+            Self::Tui => tui::run(db.conn().await?, options)
+                .await
+                .map_err(|err| Error::Tui(Box::new(err))),
+            Self::Import(import) => handle_import(db.conn().await?, options, import, out).await,
+            Self::Export(export) => handle_export(db.conn().await?, options, export, out).await,
+            Self::Backup(path) => handle_backup(db.conn().await?, path, out).await,
+            Self::Restore(restore) => handle_restore(db.conn().await?, restore, out).await,
+            Self::Git(git) => handle_git(db, options, git, out).await,
+            Self::Prompt(format) => {
+                // prompts are rendered constantly, so avoid the database while the cached state is fresh
+                let state = match State::load_fresh() {
+                    Some(state) => state,
+                    None => State::refresh(db.conn().await?).await?,
+                };
+                print_prompt(&state, format.as_deref(), options, out)
+            }
+            // the daemon opens the database itself, and keeps it open
+            #[cfg(unix)]
+            Self::Daemon => Ok(crate::daemon::serve().await?),
+            #[cfg(not(unix))]
+            Self::Daemon => Err(Error::Unsupported("the daemon")),
+            Self::Serve(listen) => crate::server::serve(listen, options.clone())
+                .await
+                .map_err(|err| Error::Server(Box::new(err))),
+            Self::Report(report) => handle_report(db.conn().await?, options, report, out).await,
+            Self::EventsList(date) => handle_events_list(db.conn().await?, date, out).await,
+            Self::EventRm(id) => handle_event_rm(db.conn().await?, id).await,
+            Self::TaskSplit(split) => handle_task_split(db.conn().await?, split, out).await,
+            Self::TaskMerge(merge) => handle_task_merge(db.conn().await?, merge, out).await,
+            Self::TaskUndo => handle_task_undo(db.conn().await?, out).await,
         }
     }
 }
 
+fn truncate_message(message: &str) -> String {
+    let mut t = message.to_owned();
+    if message.len() > 40 {
+        t.truncate(39);
+        t.push('…');
+    }
+    t
+}
+
+/// Convert a refused insertion into a description of the tasks it would have reshaped.
+async fn describe_reshape<T>(
+    conn: &mut SqliteConnection,
+    result: Result<T, db::Error>,
+) -> Result<T, Error> {
+    match result {
+        Err(db::Error::ReshapesHistory(starts)) => {
+            let mut tasks = Vec::with_capacity(starts.len());
+            for start in starts {
+                let stop = RetrieveEvent::first_after(conn, start.timestamp).await?;
+                tasks.push(Task {
+                    start: start.timestamp.into(),
+                    stopped: stop
+                        .as_ref()
+                        .is_some_and(|stop| matches!(stop.evt_type, EvtType::Stop)),
+                    stop: stop.map(|stop| stop.timestamp.into()),
+                    id: start.id,
+                    message: start.message,
+                    implicit_stop: false,
+                    pomodoro: false,
+                });
+            }
+            Err(Error::ReshapesHistory(tasks))
+        }
+        result => result.map_err(Into::into),
+    }
+}
+
 async fn handle_start_stop(
     conn: &mut SqliteConnection,
+    options: &Options,
     evt_type: db::EvtType,
     Event { timestamp, message }: Event,
+    out: &mut dyn Write,
 ) -> Result<(), Error> {
-    let truncated_message = {
-        let mut t = message.clone();
-        if message.len() > 40 {
-            t.truncate(39);
-            t.push('…');
-        }
-        t
-    };
+    let truncated_message = truncate_message(&message);
 
     let db_evt = db::InsertEvent {
         evt_type,
         timestamp: timestamp.into(),
         message,
+        provisional: false,
     };
-    let record_number = db_evt.insert(conn).await?;
+    let inserted = db_evt.insert(conn, options.overlap).await;
+    let record_number = describe_reshape(conn, inserted).await?;
 
     // output for a start or stop event
     // TODO: return this instead of emitting it here in the library code
     let formatted_timestamp = timestamp.format("%Y-%m-%d %H%M");
     let evt_type_name = evt_type.name();
-    println!("[{formatted_timestamp}] #{record_number}: {evt_type_name} {truncated_message}");
+    writeln!(
+        out,
+        "[{formatted_timestamp}] #{record_number}: {evt_type_name} {truncated_message}"
+    )?;
 
     Ok(())
 }
 
-struct Task {
-    start: DateTime<Local>,
-    stop: Option<DateTime<Local>>,
-    id: Id,
+async fn handle_start_interactive(
+    conn: &mut SqliteConnection,
+    options: &Options,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let now = Utc::now();
+    let candidates = picker::recent(conn, now - options.config.picker.recent, now).await?;
+    let Some(message) = picker::pick(&candidates)? else {
+        writeln!(out, "nothing started")?;
+        return Ok(());
+    };
+    let evt = Event {
+        timestamp: Local::now(),
+        message,
+    };
+    handle_start_stop(conn, options, EvtType::Start, evt, out).await
+}
+
+async fn handle_worked(
+    conn: &mut SqliteConnection,
+    options: &Options,
+    Span {
+        start,
+        stop,
+        message,
+    }: Span,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    if stop <= start {
+        return Err(Error::EmptySpan);
+    }
+
+    // look for anything this span will reshape before inserting it
+    let mut overlapping = RetrieveEvent::events_between(conn, start.into(), stop.into()).await?;
+    // the STOP of a task which ends just as this span starts doesn't overlap it
+    overlapping.retain(|event| {
+        !(matches!(event.evt_type, EvtType::Stop) && event.timestamp == start.with_timezone(&Utc))
+    });
+    let interrupted = db::in_progress_at(conn, start.into()).await?;
+
+    let truncated_message = truncate_message(&message);
+    let inserted = db::insert_range(
+        conn,
+        db::InsertEvent {
+            evt_type: EvtType::Start,
+            timestamp: start.into(),
+            message,
+            provisional: false,
+        },
+        db::InsertEvent {
+            evt_type: EvtType::Stop,
+            timestamp: stop.into(),
+            message: String::new(),
+            provisional: false,
+        },
+        options.overlap,
+    )
+    .await;
+    let (start_id, stop_id) = match describe_reshape(conn, inserted).await {
+        Err(err @ Error::ReshapesHistory(_)) => {
+            // explain the refusal
+            write_overlap_warnings(interrupted, &overlapping, out)?;
+            return Err(err);
+        }
+        result => result?,
+    };
+
+    let formatted_start = start.format("%Y-%m-%d %H%M");
+    let formatted_stop = stop.format("%H%M");
+    writeln!(
+        out,
+        "[{formatted_start}–{formatted_stop}] #{start_id}, #{stop_id}: {truncated_message}"
+    )?;
+    write_overlap_warnings(interrupted, &overlapping, out)?;
+
+    Ok(())
+}
+
+/// Warn about the task a span interrupts, and the events it overlaps.
+fn write_overlap_warnings(
+    interrupted: Option<RetrieveEvent>,
+    overlapping: &[RetrieveEvent],
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    if let Some(RetrieveEvent { id, message, .. }) = interrupted {
+        writeln!(out, "warning: this range interrupts #{id}: {message}")?;
+    }
+    if !overlapping.is_empty() {
+        writeln!(out, "warning: this range overlaps existing events:")?;
+        for RetrieveEvent {
+            id,
+            evt_type,
+            timestamp,
+            message,
+            ..
+        } in overlapping
+        {
+            let timestamp: DateTime<Local> = (*timestamp).into();
+            let timestamp = timestamp.format("%H%M%S");
+            let evt_type = evt_type.name();
+            writeln!(out, "  #{id} {timestamp}: {evt_type} {message}")?;
+        }
+    }
+    Ok(())
+}
+
+async fn handle_timebox(
+    conn: &mut SqliteConnection,
+    options: &Options,
+    Span {
+        start,
+        stop,
+        message,
+    }: Span,
+    out: &mut dyn Write,
+) -> Result<(Id, Id), Error> {
+    if stop <= start {
+        return Err(Error::EmptySpan);
+    }
+
+    let truncated_message = truncate_message(&message);
+    let inserted = db::insert_range(
+        conn,
+        db::InsertEvent {
+            evt_type: EvtType::Start,
+            timestamp: start.into(),
+            message,
+            provisional: false,
+        },
+        db::InsertEvent {
+            evt_type: EvtType::Stop,
+            timestamp: stop.into(),
+            message: String::new(),
+            provisional: true,
+        },
+        options.overlap,
+    )
+    .await;
+    let (start_id, stop_id) = describe_reshape(conn, inserted).await?;
+
+    let formatted_start = start.format("%Y-%m-%d %H%M");
+    let formatted_stop = stop.format("%Y-%m-%d %H%M");
+    writeln!(
+        out,
+        "[{formatted_start}] #{start_id}: START {truncated_message}"
+    )?;
+    writeln!(out, "[{formatted_stop}] #{stop_id}: STOP (scheduled)")?;
+
+    Ok((start_id, stop_id))
+}
+
+/// Timebox a task for the length of a pomodoro, then record it as completed and take a break.
+///
+/// If the task is stopped or replaced before the pomodoro ends, it doesn't count.
+async fn handle_pomodoro(
+    conn: &mut SqliteConnection,
+    options: &Options,
     message: String,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let config = &options.config.pomodoro;
+    let sender = config
+        .notify
+        .then(|| notify::Sender::from(&options.config.notify));
+
+    let start = Local::now();
+    let stop = start + config.work;
+    let truncated_message = truncate_message(&message);
+    let span = Span {
+        start,
+        stop,
+        message: message.clone(),
+    };
+    let (start_id, _) = handle_timebox(conn, options, span, out).await?;
+    State::refresh(conn).await?;
+    let notification = Notification {
+        summary: format!("pomodoro until {}", stop.format("%H:%M")),
+        body: truncated_message.clone(),
+    };
+    announce(sender.as_ref(), notification, out)?;
+
+    let completed = wait_until(stop).await;
+    let now = Utc::now();
+    let undisturbed = RetrieveEvent::events_between(conn, start.into(), now.min(stop.into()))
+        .await?
+        .iter()
+        .all(|event| event.id == start_id);
+    if !undisturbed {
+        writeln!(
+            out,
+            "#{start_id} was stopped or replaced before the pomodoro ended"
+        )?;
+        return Ok(());
+    }
+    if !completed {
+        let stop_id = db::InsertEvent {
+            evt_type: EvtType::Stop,
+            timestamp: now,
+            message: String::new(),
+            provisional: false,
+        }
+        .insert(conn, options.overlap)
+        .await?;
+        let now_local: DateTime<Local> = now.into();
+        writeln!(
+            out,
+            "[{}] #{stop_id}: STOP {truncated_message}",
+            now_local.format("%Y-%m-%d %H%M")
+        )?;
+        writeln!(out, "pomodoro abandoned")?;
+        return Ok(());
+    }
+
+    let stop_id = db::complete_pomodoro(conn, start_id, stop.into()).await?;
+    State::refresh(conn).await?;
+    writeln!(
+        out,
+        "[{}] #{stop_id}: STOP {truncated_message}",
+        stop.format("%Y-%m-%d %H%M")
+    )?;
+
+    let (tasks, _) = tasks_in(conn, options, Period::day(stop.date_naive())).await?;
+    let pomodoros = tasks.iter().filter(|task| task.pomodoro);
+    let today = pomodoros.clone().count();
+    let this_task = pomodoros.filter(|task| task.message == message).count();
+
+    let long = config.long_break_every > 0 && today % config.long_break_every == 0;
+    let length = if long {
+        config.long_break
+    } else {
+        config.short_break
+    };
+    let break_end = Local::now() + length;
+    let notification = Notification {
+        summary: format!(
+            "pomodoro complete; break until {}",
+            break_end.format("%H:%M")
+        ),
+        body: format!("{this_task} 🍅 today for {truncated_message}, {today} in all"),
+    };
+    announce(sender.as_ref(), notification, out)?;
+
+    if wait_until(break_end).await {
+        let notification = Notification {
+            summary: "break over".into(),
+            body: String::new(),
+        };
+        announce(sender.as_ref(), notification, out)?;
+    }
+    Ok(())
 }
 
-impl Task {
-    fn duration(&self) -> Option<Duration> {
-        self.stop.map(|stop| stop - self.start)
+/// Print a notification's summary, and send it if there's a sender.
+///
+/// A missing notification service is reported, but isn't an error.
+fn announce(
+    sender: Option<&notify::Sender>,
+    notification: Notification,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    writeln!(out, "{}", notification.summary)?;
+    for line in notification.body.lines() {
+        writeln!(out, "  {line}")?;
     }
+    if let Some(sender) = sender {
+        if let Err(err) = sender.send(&notification) {
+            writeln!(out, "failed to send notification: {err}")?;
+        }
+    }
+    Ok(())
 }
 
-impl fmt::Display for Task {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let start = self.start.format("%H%M");
-        let stop = self
-            .stop
-            .map(|stop| stop.format("%H%M").to_string())
-            .unwrap_or(String::from("…   "));
-        let duration = self.duration().unwrap_or(Duration::zero());
-        let minutes = duration.num_minutes();
-        let hours = minutes / 60;
-        let minutes = minutes % 60;
-        let id = self.id;
-        let message = &self.message;
+/// Wait until `time`, returning `false` if interrupted first.
+async fn wait_until(time: DateTime<Local>) -> bool {
+    let duration = (time - Local::now()).to_std().unwrap_or_default();
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => true,
+        _ = tokio::signal::ctrl_c() => false,
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    let hours = minutes / 60;
+    let minutes = minutes % 60;
+    format!("{hours}:{minutes:02}")
+}
+
+/// Print the task in progress for a shell prompt.
+///
+/// `format` overrides the configured format.
+fn print_prompt(
+    state: &State,
+    format: Option<&str>,
+    options: &Options,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let prompt = &options.config.prompt;
+    let format = format.unwrap_or(&prompt.format);
+    writeln!(out, "{}", state.render(format, &prompt.idle, Utc::now()))?;
+    Ok(())
+}
+
+/// The START of the task in progress at `now`, if any.
+async fn current_task(
+    conn: &mut SqliteConnection,
+    now: DateTime<Utc>,
+) -> Result<Option<RetrieveEvent>, Error> {
+    Ok(RetrieveEvent::last_before(conn, now)
+        .await?
+        .filter(|event| matches!(event.evt_type, EvtType::Start)))
+}
 
-        write!(
-            f,
-            "[{start}–{stop}] ({hours}:{minutes:02}) #{id}: {message}"
-        )
+async fn handle_status(conn: &mut SqliteConnection, out: &mut dyn Write) -> Result<(), Error> {
+    let now = Utc::now();
+    let current = current_task(conn, now).await?;
+
+    let Some(RetrieveEvent {
+        id,
+        timestamp,
+        message,
+        ..
+    }) = current
+    else {
+        writeln!(out, "not working on anything")?;
+        return Ok(());
+    };
+
+    let elapsed = format_duration(now - timestamp);
+    let scheduled_stop = RetrieveEvent::first_after(conn, now)
+        .await?
+        .filter(|event| event.provisional && matches!(event.evt_type, EvtType::Stop));
+
+    match scheduled_stop {
+        Some(stop) => {
+            let remaining = format_duration(stop.timestamp - now);
+            writeln!(
+                out,
+                "#{id}: {message} ({elapsed} elapsed, {remaining} remaining)"
+            )?;
+        }
+        None => writeln!(out, "#{id}: {message} ({elapsed} elapsed)")?,
     }
+
+    Ok(())
 }
 
 fn midnight_of(date: NaiveDate) -> Result<DateTime<Utc>, Error> {
@@ -120,79 +815,260 @@ fn midnight_of(date: NaiveDate) -> Result<DateTime<Utc>, Error> {
     Ok(dt)
 }
 
-async fn handle_report(conn: &mut SqliteConnection, date: NaiveDate) -> Result<(), Error> {
-    // get the list of events for the report period
-    let local_midnight = midnight_of(date)?;
-    let next_day = local_midnight + Duration::days(1);
-    let events = RetrieveEvent::events_between(conn, local_midnight, next_day).await?;
+/// Reconstruct the tasks in `period`.
+///
+/// Runaway tasks are treated as having stopped at the end of the workday.
+/// Also return whether any task was stopped that way.
+pub(crate) async fn tasks_in(
+    conn: &mut SqliteConnection,
+    options: &Options,
+    period: Period,
+) -> Result<(Vec<Task>, bool), Error> {
+    let (start, end) = period.bounds()?;
+    let events = RetrieveEvent::events_between(conn, start, end).await?;
+    let pomodoros = db::pomodoros_between(conn, start, end).await?;
+    Ok(build_tasks(events, &pomodoros, options))
+}
 
-    // transform into a list of events for the report period
-    let mut tasks = Vec::with_capacity(events.len());
+/// Reconstruct every task ever logged, as [`tasks_in`] does for a period.
+pub(crate) async fn all_tasks(
+    conn: &mut SqliteConnection,
+    options: &Options,
+) -> Result<Vec<Task>, Error> {
+    let events = RetrieveEvent::all(conn).await?;
+    let pomodoros = db::all_pomodoros(conn).await?;
+    Ok(build_tasks(events, &pomodoros, options).0)
+}
 
-    let mut in_progress: Option<Task> = None;
-    for event in events {
-        if let Some(mut in_progress) = in_progress.take() {
-            in_progress.stop = Some(event.timestamp.into());
-            tasks.push(in_progress);
-        }
-        if let EvtType::Start = event.evt_type {
-            in_progress = Some(Task {
-                start: event.timestamp.into(),
-                stop: None,
-                id: event.id,
-                message: event.message,
-            });
+/// Reconstruct the tasks from `events`, marking those begun by the STARTs in `pomodoros`.
+fn build_tasks(
+    events: Vec<RetrieveEvent>,
+    pomodoros: &[Id],
+    options: &Options,
+) -> (Vec<Task>, bool) {
+    let mut tasks = Task::from_events(events);
+    let now = Local::now();
+    let mut any_implicit = false;
+    for task in &mut tasks {
+        any_implicit |= task.apply_limits(&options.config.workday, now);
+        task.pomodoro = pomodoros.contains(&task.id);
+    }
+    (tasks, any_implicit)
+}
+
+pub(crate) fn total_duration(tasks: &[Task]) -> Duration {
+    tasks
+        .iter()
+        .map(|task| task.duration().unwrap_or(Duration::zero()))
+        .fold(Duration::zero(), |total, item| total + item)
+}
+
+/// The commits authored during each task, from every repository in `repos`.
+fn commits_during(tasks: &[Task], repos: &[PathBuf]) -> Result<Vec<Vec<git::Commit>>, Error> {
+    let mut during = vec![Vec::new(); tasks.len()];
+    let (Some(first), Some(last)) = (tasks.first(), tasks.last()) else {
+        return Ok(during);
+    };
+    let now = Local::now();
+    let window = |task: &Task| (task.start, task.stop.unwrap_or(now));
+
+    for repo in repos {
+        let commits = git::commits_between(repo, first.start.into(), window(last).1.into())?;
+        for commit in commits {
+            let author_time = commit.author_time;
+            if let Some(idx) = tasks.iter().position(|task| {
+                let (start, stop) = window(task);
+                start <= author_time && author_time < stop
+            }) {
+                during[idx].push(commit);
+            }
         }
     }
-    // we might have a final event in progress
-    if let Some(in_progress) = in_progress {
-        tasks.push(in_progress);
+
+    for commits in &mut during {
+        commits.sort_by_key(|commit| commit.author_time);
     }
+    Ok(during)
+}
 
-    // now emit all tasks
-    println!("{}:", date.format("%Y-%m-%d"));
-    println!("-----------");
-    for task in &tasks {
-        println!("{task}");
+async fn handle_report(
+    conn: &mut SqliteConnection,
+    options: &Options,
+    Report {
+        period,
+        style,
+        repos,
+    }: Report,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let mut any_implicit = false;
+    let mut n_total = 0;
+    let mut grand_total = Duration::zero();
+
+    for date in period.days() {
+        let (tasks, implicit) = tasks_in(conn, options, Period::day(date)).await?;
+        any_implicit |= implicit;
+
+        // multi-day reports omit days with nothing to report
+        if tasks.is_empty() && period.first != period.last {
+            continue;
+        }
+
+        let commits = commits_during(&tasks, &repos)?;
+        let total = total_duration(&tasks);
+        match style {
+            ReportStyle::Text => {
+                // now emit all tasks
+                writeln!(out, "{}:", date.format("%Y-%m-%d"))?;
+                writeln!(out, "-----------")?;
+                for (task, commits) in tasks.iter().zip(&commits) {
+                    writeln!(out, "{task}")?;
+                    for git::Commit { hash, summary, .. } in commits {
+                        writeln!(out, "      {hash} {summary}")?;
+                    }
+                }
+                writeln!(out, "-----------")?;
+                let n = tasks.len();
+                let minutes = total.num_minutes();
+                let hours = minutes / 60;
+                let minutes = minutes % 60;
+                let pomodoros = match tasks.iter().filter(|task| task.pomodoro).count() {
+                    0 => String::new(),
+                    n => format!("   {n:2} 🍅"),
+                };
+                writeln!(out, " {n:2} tasks   {hours:2}:{minutes:02}{pomodoros}")?;
+            }
+            ReportStyle::Markdown => {
+                writeln!(out, "## {}", date.format("%Y-%m-%d %A"))?;
+                writeln!(out)?;
+                write!(out, "{}", Standup::new(&tasks, &commits, &options.config))?;
+                writeln!(out)?;
+                writeln!(out, "Total: {}", format_duration(total))?;
+                writeln!(out)?;
+            }
+        }
+
+        n_total += tasks.len();
+        grand_total += total;
+    }
+
+    if period.first != period.last {
+        match style {
+            ReportStyle::Text => {
+                let minutes = grand_total.num_minutes();
+                let hours = minutes / 60;
+                let minutes = minutes % 60;
+                writeln!(out, "===========")?;
+                writeln!(out, " {n_total:2} tasks   {hours:2}:{minutes:02}")?;
+            }
+            ReportStyle::Markdown => {
+                writeln!(out, "**Total: {}**", format_duration(grand_total))?;
+                writeln!(out)?;
+            }
+        }
+    }
+    if any_implicit {
+        writeln!(out, "  * implicitly stopped at the end of the workday")?;
     }
-    println!("-----------");
-    let n = tasks.len();
-    let total: Duration = tasks
-        .iter()
-        .map(|task| task.duration().unwrap_or(Duration::zero()))
-        .fold(Duration::zero(), |total, item| total + item);
-    let minutes = total.num_minutes();
-    let hours = minutes / 60;
-    let minutes = minutes % 60;
-    println!(" {n:2} tasks   {hours:2}:{minutes:02}");
 
     Ok(())
 }
 
-async fn handle_events_list(conn: &mut SqliteConnection, date: NaiveDate) -> Result<(), Error> {
+/// A day's tasks as a Markdown list for a standup, with the commits made during each as sub-bullets.
+///
+/// Tasks with the same message are combined into a single item.
+struct Standup<'a> {
+    items: Vec<StandupItem<'a>>,
+    config: &'a Config,
+}
+
+struct StandupItem<'a> {
+    message: &'a str,
+    duration: Duration,
+    implicit_stop: bool,
+    pomodoros: usize,
+    commits: Vec<&'a git::Commit>,
+}
+
+impl<'a> Standup<'a> {
+    fn new(tasks: &'a [Task], commits: &'a [Vec<git::Commit>], config: &'a Config) -> Self {
+        let mut items: Vec<StandupItem> = Vec::new();
+        for (task, commits) in tasks.iter().zip(commits) {
+            let idx = match items.iter().position(|item| item.message == task.message) {
+                Some(idx) => idx,
+                None => {
+                    items.push(StandupItem {
+                        message: &task.message,
+                        duration: Duration::zero(),
+                        implicit_stop: false,
+                        pomodoros: 0,
+                        commits: Vec::new(),
+                    });
+                    items.len() - 1
+                }
+            };
+            let item = &mut items[idx];
+            item.duration += task.duration().unwrap_or(Duration::zero());
+            item.implicit_stop |= task.implicit_stop;
+            item.pomodoros += usize::from(task.pomodoro);
+            item.commits.extend(commits);
+        }
+        Self { items, config }
+    }
+}
+
+impl fmt::Display for Standup<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.items {
+            let duration = format_duration(item.duration);
+            let implicit = if item.implicit_stop { "*" } else { "" };
+            let pomodoros = match item.pomodoros {
+                0 => String::new(),
+                n => format!(", {n} 🍅"),
+            };
+            write!(f, "- {} ({duration}{implicit}{pomodoros})", item.message)?;
+            for link in self.config.links.links(item.message) {
+                write!(f, " <{link}>")?;
+            }
+            writeln!(f)?;
+            for git::Commit { hash, summary, .. } in &item.commits {
+                writeln!(f, "  - `{hash}` {summary}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn handle_events_list(
+    conn: &mut SqliteConnection,
+    date: NaiveDate,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
     // get the list of events for the report period
     let local_midnight = midnight_of(date)?;
     let next_day = local_midnight + Duration::days(1);
     let events = RetrieveEvent::events_between(conn, local_midnight, next_day).await?;
 
     // now emit all events
-    println!("{}:", date.format("%Y-%m-%d"));
-    println!("-----------");
+    writeln!(out, "{}:", date.format("%Y-%m-%d"))?;
+    writeln!(out, "-----------")?;
     for event in &events {
         let RetrieveEvent {
             id,
             evt_type,
             timestamp,
             message,
+            provisional,
         } = event;
 
         let timestamp: DateTime<Local> = (*timestamp).into();
         let timestamp = timestamp.format("%H%M%S");
         let evt_type = evt_type.name();
+        let scheduled = if *provisional { " (scheduled)" } else { "" };
 
-        println!("#{id} {timestamp}: {evt_type} {message}");
+        writeln!(out, "#{id} {timestamp}: {evt_type}{scheduled} {message}")?;
     }
-    println!("-----------");
+    writeln!(out, "-----------")?;
 
     Ok(())
 }
@@ -204,10 +1080,700 @@ async fn handle_event_rm(conn: &mut SqliteConnection, id: Id) -> Result<(), Erro
         .map_err(Into::into)
 }
 
+async fn handle_task_split(
+    conn: &mut SqliteConnection,
+    Split { id, at, message }: Split,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let description = format!("split #{id} at {}", at.format("%Y-%m-%d %H%M"));
+    let at = at.into();
+    edit::undoable(conn, &description, |conn| {
+        Box::pin(edit::split_task(conn, id, at, message))
+    })
+    .await?;
+    writeln!(out, "{description}")?;
+    Ok(())
+}
+
+async fn handle_task_merge(
+    conn: &mut SqliteConnection,
+    Merge {
+        first,
+        second,
+        message,
+    }: Merge,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let description = format!("merged #{second} into #{first}");
+    edit::undoable(conn, &description, |conn| {
+        Box::pin(edit::merge_tasks(conn, first, second, message))
+    })
+    .await?;
+    writeln!(out, "{description}")?;
+    Ok(())
+}
+
+async fn handle_task_undo(conn: &mut SqliteConnection, out: &mut dyn Write) -> Result<(), Error> {
+    match edit::undo(conn).await? {
+        Some(description) => writeln!(out, "undid: {description}")?,
+        None => writeln!(out, "nothing to undo")?,
+    }
+    Ok(())
+}
+
+/// Displays a list of tasks, one per line, each on a new line.
+struct TaskList<'a>(&'a [Task]);
+
+impl fmt::Display for TaskList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for task in self.0 {
+            let date = task.start.format("%Y-%m-%d");
+            write!(f, "\n  {date} {task}")?;
+        }
+        Ok(())
+    }
+}
+
+async fn handle_autostop(
+    conn: &mut SqliteConnection,
+    options: &Options,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let workday = &options.config.workday;
+    if workday.end.is_none() && workday.max_task_length.is_none() {
+        writeln!(out, "no workday end or maximum task length is configured")?;
+        return Ok(());
+    }
+
+    let events = RetrieveEvent::all(conn).await?;
+    let now = Local::now();
+    let mut stopped = 0;
+    for mut task in Task::from_events(events) {
+        if !task.apply_limits(workday, now) {
+            continue;
+        }
+        let stop = task.stop.expect("limited tasks always have a stop");
+        let db_evt = db::InsertEvent {
+            evt_type: EvtType::Stop,
+            timestamp: stop.into(),
+            message: String::new(),
+            provisional: false,
+        };
+        // we're deliberately truncating this task
+        let stop_id = db_evt.insert(conn, Overlap::Allow).await?;
+        let formatted_stop = stop.format("%Y-%m-%d %H%M");
+        let id = task.id;
+        let truncated_message = truncate_message(&task.message);
+        writeln!(
+            out,
+            "[{formatted_stop}] #{stop_id}: STOP #{id} {truncated_message}"
+        )?;
+        stopped += 1;
+    }
+    writeln!(out, " {stopped:2} tasks stopped")?;
+
+    Ok(())
+}
+
+async fn handle_watch(
+    conn: &mut SqliteConnection,
+    options: &Options,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let source = idle::Source::from(&options.config.idle);
+    let threshold = options.config.idle.threshold;
+    writeln!(out, "watching for idle time; interrupt to stop")?;
+
+    let mut last_poll = Utc::now();
+    let mut away_since = None;
+    let mut reminders = Reminders::default();
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(idle::POLL) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+        let now = Utc::now();
+        send_reminders(conn, options, &mut reminders, last_poll, now, out).await?;
+        // a long gap between polls means the computer was asleep
+        let slept = (now - last_poll >= threshold).then_some(last_poll);
+        last_poll = now;
+
+        let idle = match source.idle_since(threshold, now) {
+            Ok(idle) => idle,
+            Err(err) => {
+                // one failed check shouldn't end the watch; keep any sleep in mind for the next one
+                writeln!(out, "failed to check idle time: {err}")?;
+                away_since = away_since.or(slept);
+                continue;
+            }
+        };
+        match (away_since.or(slept), idle) {
+            (None, Some(since)) => {
+                let since_local: DateTime<Local> = since.into();
+                writeln!(out, "idle since {}", since_local.format("%H:%M"))?;
+                away_since = Some(since);
+            }
+            (Some(since), None) => {
+                away_since = None;
+                handle_return(conn, since, now, out).await?;
+                State::refresh(conn).await?;
+            }
+            (away, Some(_)) => away_since = away,
+            (None, None) => {}
+        }
+    }
+}
+
+/// Send whichever configured notifications are due, given that the last check was at `previous`.
+async fn send_reminders(
+    conn: &mut SqliteConnection,
+    options: &Options,
+    reminders: &mut Reminders,
+    previous: DateTime<Utc>,
+    now: DateTime<Utc>,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let config = &options.config.notify;
+    let workday = &options.config.workday;
+    let mut notifications = Vec::new();
+
+    let state = State::query(conn).await?;
+    let task = state.task_at(now);
+    if let Some(limit) = config.long_task {
+        notifications.extend(reminders.long_task(task, limit, now));
+    }
+    if let (Some(after), None) = (config.nothing_running, task) {
+        let stopped = RetrieveEvent::last_before(conn, now)
+            .await?
+            .map(|event| event.timestamp);
+        notifications.extend(reminders.nothing_running(stopped, workday, after, now.into()));
+    }
+    if let Some(end) = workday.end.filter(|_| config.end_of_day) {
+        if notify::crossed(end, previous.into(), now.into()) {
+            notifications.push(end_of_day_summary(conn, options).await?);
+        }
+    }
+
+    let sender = notify::Sender::from(config);
+    for notification in notifications {
+        announce(Some(&sender), notification, out)?;
+    }
+    Ok(())
+}
+
+/// Today's total, and the time spent on each task.
+async fn end_of_day_summary(
+    conn: &mut SqliteConnection,
+    options: &Options,
+) -> Result<Notification, Error> {
+    let today = Local::now().date_naive();
+    let (tasks, _) = tasks_in(conn, options, Period::day(today)).await?;
+    let commits = vec![Vec::new(); tasks.len()];
+    let standup = Standup::new(&tasks, &commits, &options.config);
+    let body = standup
+        .items
+        .iter()
+        .map(|item| format!("{} ({})", item.message, format_duration(item.duration)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(Notification {
+        summary: format!("worked {} today", format_duration(total_duration(&tasks))),
+        body,
+    })
+}
+
+/// Ask what to do with the time spent away, if a task was running throughout.
+async fn handle_return(
+    conn: &mut SqliteConnection,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let away = format_duration(now - since);
+    let task = current_task(conn, since).await?;
+    // the task may have been stopped or replaced in the meantime
+    let interrupted = !RetrieveEvent::events_between(conn, since, now)
+        .await?
+        .is_empty();
+    let Some(task) = task.filter(|_| !interrupted) else {
+        writeln!(out, "back after {away}")?;
+        return Ok(());
+    };
+
+    let since_local: DateTime<Local> = since.into();
+    let id = task.id;
+    let truncated_message = truncate_message(&task.message);
+    writeln!(
+        out,
+        "back after {away}, idle since {} during #{id}: {truncated_message}",
+        since_local.format("%H:%M")
+    )?;
+    let answer = interact::ask("[k]eep that time, [d]iscard it, or [r]eassign it?")?;
+    match answer.to_lowercase().as_str() {
+        "d" | "discard" => {
+            let stop_id = db::InsertEvent {
+                evt_type: EvtType::Stop,
+                timestamp: since,
+                message: String::new(),
+                provisional: false,
+            }
+            // we're deliberately truncating this task
+            .insert(conn, Overlap::Allow)
+            .await?;
+            writeln!(
+                out,
+                "[{}] #{stop_id}: STOP {truncated_message}",
+                since_local.format("%Y-%m-%d %H%M")
+            )?;
+        }
+        "r" | "reassign" => {
+            let message = interact::ask("what were you doing?")?;
+            if message.is_empty() {
+                writeln!(out, "kept")?;
+                return Ok(());
+            }
+            let reassigned_message = truncate_message(&message);
+            // the reassigned task splits this one, which resumes on return
+            let (reassigned_id, resumed_id) = db::insert_range(
+                conn,
+                db::InsertEvent {
+                    evt_type: EvtType::Start,
+                    timestamp: since,
+                    message,
+                    provisional: false,
+                },
+                db::InsertEvent {
+                    evt_type: EvtType::Start,
+                    timestamp: now,
+                    message: task.message,
+                    provisional: false,
+                },
+                Overlap::Allow,
+            )
+            .await?;
+            let now_local: DateTime<Local> = now.into();
+            writeln!(
+                out,
+                "[{}] #{reassigned_id}: START {reassigned_message}",
+                since_local.format("%Y-%m-%d %H%M")
+            )?;
+            writeln!(
+                out,
+                "[{}] #{resumed_id}: START {truncated_message}",
+                now_local.format("%Y-%m-%d %H%M")
+            )?;
+        }
+        _ => writeln!(out, "kept")?,
+    }
+
+    Ok(())
+}
+
+async fn handle_import(
+    conn: &mut SqliteConnection,
+    options: &Options,
+    Import {
+        format,
+        path,
+        dry_run,
+    }: Import,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let intervals = format.read(&path)?;
+    let ImportSummary {
+        imported,
+        duplicates,
+        conflicts,
+    } = interchange::import(conn, intervals, options.overlap, dry_run).await?;
+
+    let verb = if dry_run { "would import" } else { "imported" };
+    writeln!(out, "{verb} {imported} intervals")?;
+    if duplicates > 0 {
+        writeln!(
+            out,
+            "skipped {duplicates} intervals which were already imported"
+        )?;
+    }
+    if conflicts > 0 {
+        writeln!(
+            out,
+            "skipped {conflicts} intervals which would split or truncate existing tasks; use --force to import them anyway"
+        )?;
+    }
+
+    Ok(())
+}
+
+async fn handle_export(
+    conn: &mut SqliteConnection,
+    options: &Options,
+    Export { format, period }: Export,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let tasks = match period {
+        Some(period) => tasks_in(conn, options, period).await?.0,
+        None => all_tasks(conn, options).await?,
+    };
+
+    format.write(&tasks, &options.config, out)?;
+    Ok(())
+}
+
+async fn handle_backup(
+    conn: &mut SqliteConnection,
+    path: Option<PathBuf>,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let backup = Backup::dump(conn).await?;
+    match path {
+        Some(path) => {
+            let mut file = std::fs::File::create(&path)?;
+            backup.write(&mut file)?;
+            let n = backup.events.len();
+            let path = path.display();
+            writeln!(out, "backed up {n} events to {path}")?;
+        }
+        None => backup.write(out)?,
+    }
+    Ok(())
+}
+
+async fn handle_restore(
+    conn: &mut SqliteConnection,
+    Restore { path, mode }: Restore,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let RestoreSummary {
+        restored,
+        duplicates,
+        deleted,
+    } = Backup::read(&path)?.restore(conn, mode).await?;
+
+    if deleted > 0 {
+        writeln!(out, "deleted {deleted} existing events")?;
+    }
+    writeln!(out, "restored {restored} events")?;
+    if duplicates > 0 {
+        writeln!(
+            out,
+            "skipped {duplicates} events which were already present"
+        )?;
+    }
+    Ok(())
+}
+
+async fn handle_git(
+    db: &mut Db,
+    options: &Options,
+    git: Git,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    match git {
+        Git::InstallHooks => {
+            let path = git::install_hooks()?;
+            let path = path.display();
+            writeln!(out, "installed {path}")?;
+        }
+        Git::UninstallHooks => match git::uninstall_hooks()? {
+            Some(path) => {
+                let path = path.display();
+                writeln!(out, "removed {path}")?;
+            }
+            None => writeln!(out, "no hook to remove")?,
+        },
+        Git::PostCheckout { branch_checkout } => {
+            if !branch_checkout {
+                return Ok(());
+            }
+            let Some(message) =
+                git::current_branch()?.and_then(|branch| options.config.git.message_for(&branch))
+            else {
+                return Ok(());
+            };
+
+            let conn = db.conn().await?;
+            let current = current_task(conn, Utc::now()).await?;
+            if current.is_some_and(|current| current.message == message) {
+                writeln!(out, "already working on {message}")?;
+                return Ok(());
+            }
+
+            let evt = Event {
+                timestamp: Local::now(),
+                message,
+            };
+            handle_start_stop(conn, options, EvtType::Start, evt, out).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn handle_doctor(
+    conn: &mut SqliteConnection,
+    options: &Options,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let anomalies = doctor::scan(conn, &options.config.workday).await?;
+    if anomalies.is_empty() {
+        writeln!(out, "no anomalies found")?;
+        return Ok(());
+    }
+
+    for anomaly in &anomalies {
+        writeln!(out, "{anomaly}")?;
+    }
+    writeln!(out, "-----------")?;
+    writeln!(out, " {:2} anomalies", anomalies.len())?;
+
+    for anomaly in anomalies {
+        writeln!(out)?;
+        writeln!(out, "{anomaly}")?;
+        fix_anomaly(conn, anomaly, out).await?;
+    }
+
+    Ok(())
+}
+
+/// Interactively offer to fix a single anomaly.
+async fn fix_anomaly(
+    conn: &mut SqliteConnection,
+    anomaly: Anomaly,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    match anomaly {
+        Anomaly::DoubledStop { id, .. }
+        | Anomaly::DoubledStart { id, .. }
+        | Anomaly::LeadingStop { id }
+        | Anomaly::ProvisionalLeftover { id }
+        | Anomaly::FutureEvent { id, .. } => {
+            if interact::confirm(&format!("delete #{id}?"))? && db::delete_event(conn, id).await? {
+                writeln!(out, "deleted #{id}")?;
+            }
+        }
+        Anomaly::EmptyStart { id } => {
+            let message = interact::ask(&format!("message for #{id} (blank to skip):"))?;
+            if !message.is_empty() {
+                let update = db::UpdateEvent {
+                    id,
+                    timestamp: None,
+                    message: Some(message),
+                };
+                if update.update(conn).await? {
+                    writeln!(out, "updated #{id}")?;
+                }
+            }
+        }
+        Anomaly::LongTask { id, start, .. } => {
+            let start: DateTime<Local> = start.into();
+            let day = start.format("%Y-%m-%d");
+            // a mistyped answer shouldn't abandon the remaining fixes, so ask again
+            let stop = loop {
+                let answer = interact::ask(&format!(
+                    "stop #{id} at what time on {day}? (HHMM, blank to skip)"
+                ))?;
+                if answer.is_empty() {
+                    return Ok(());
+                }
+                let stop = NaiveTime::parse_from_str(&answer, "%H%M")
+                    .ok()
+                    .and_then(|time| {
+                        Local
+                            .from_local_datetime(&start.date_naive().and_time(time))
+                            .earliest()
+                    })
+                    .filter(|stop| *stop > start);
+                match stop {
+                    Some(stop) => break stop,
+                    None => writeln!(out, "\"{answer}\" isn't a time after #{id} started")?,
+                }
+            };
+            let db_evt = db::InsertEvent {
+                evt_type: EvtType::Stop,
+                timestamp: stop.into(),
+                message: String::new(),
+                provisional: false,
+            };
+            // we're deliberately truncating this task
+            let stop_id = db_evt.insert(conn, Overlap::Allow).await?;
+            writeln!(out, "inserted STOP #{stop_id}")?;
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("ambiguous time for local midnight")]
     AmbiguousLocalMidnight,
+    #[error("time range must end after it starts")]
+    EmptySpan,
+    #[error("backing up or restoring the database")]
+    Backup(#[from] backup::Error),
+    #[error("caching the task in progress")]
+    State(#[from] state::Error),
+    #[error("integrating with git")]
+    Git(#[from] git::Error),
+    #[error("exchanging data with another time tracker")]
+    Interchange(#[from] interchange::Error),
+    #[cfg(unix)]
+    #[error("running the daemon")]
+    Daemon(#[from] crate::daemon::Error),
+    #[cfg(not(unix))]
+    #[error("{0} is not supported on this platform")]
+    Unsupported(&'static str),
+    #[error("serving the REST API")]
+    Server(#[source] Box<crate::server::Error>),
+    #[error("showing the timeline")]
+    Tui(#[source] Box<tui::Error>),
+    #[error("editing tasks")]
+    Edit(#[from] edit::Error),
+    #[error("picking a message")]
+    Picker(#[from] picker::Error),
+    #[error("detecting idle time")]
+    Idle(#[from] idle::Error),
+    #[error("interacting with the user")]
+    Interact(#[from] std::io::Error),
+    #[error("this would split or truncate existing tasks:{}", TaskList(.0))]
+    ReshapesHistory(Vec<Task>),
     #[error("executing database action")]
     Db(#[from] db::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection as _;
+
+    use super::*;
+    use crate::{
+        config::Workday,
+        db::{InsertEvent, MIGRATOR},
+    };
+
+    fn at(datetime: &str) -> DateTime<Local> {
+        Local
+            .from_local_datetime(&datetime.parse().unwrap())
+            .unwrap()
+    }
+
+    async fn with_events(events: &[(EvtType, &str)]) -> SqliteConnection {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        MIGRATOR.run(&mut conn).await.unwrap();
+        for (evt_type, datetime) in events {
+            InsertEvent {
+                evt_type: *evt_type,
+                timestamp: at(datetime).into(),
+                message: String::new(),
+                provisional: false,
+            }
+            .insert(&mut conn, Overlap::Allow)
+            .await
+            .unwrap();
+        }
+        conn
+    }
+
+    #[tokio::test]
+    async fn autostop_only_stops_runaway_tasks() {
+        let mut conn = with_events(&[
+            (EvtType::Start, "2024-05-02T17:00:00"),
+            (EvtType::Stop, "2024-05-02T19:30:00"),
+            (EvtType::Start, "2024-05-03T17:00:00"),
+            (EvtType::Start, "2024-05-04T09:00:00"),
+        ])
+        .await;
+        let mut options = Options::default();
+        options.config.workday = Workday {
+            end: NaiveTime::from_hms_opt(18, 0, 0),
+            ..Workday::default()
+        };
+
+        let mut out = Vec::new();
+        handle_autostop(&mut conn, &options, &mut out)
+            .await
+            .unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with(" 2 tasks stopped\n"));
+
+        let events = RetrieveEvent::all(&mut conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| (event.evt_type, DateTime::<Local>::from(event.timestamp)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                (EvtType::Start, at("2024-05-02T17:00:00")),
+                // the explicit stop is kept, with no stop inserted at the end of the workday
+                (EvtType::Stop, at("2024-05-02T19:30:00")),
+                (EvtType::Start, at("2024-05-03T17:00:00")),
+                (EvtType::Stop, at("2024-05-03T18:00:00")),
+                (EvtType::Start, at("2024-05-04T09:00:00")),
+                (EvtType::Stop, at("2024-05-04T18:00:00")),
+            ]
+        );
+    }
+
+    fn span(start: &str, stop: &str) -> Span {
+        Span {
+            start: at(start),
+            stop: at(stop),
+            message: "backfill".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn worked_warns_when_refused() {
+        let mut conn = with_events(&[
+            (EvtType::Start, "2024-05-02T09:00:00"),
+            (EvtType::Stop, "2024-05-02T10:00:00"),
+            (EvtType::Start, "2024-05-02T11:00:00"),
+        ])
+        .await;
+
+        let mut out = Vec::new();
+        let span = span("2024-05-02T09:30:00", "2024-05-02T10:30:00");
+        let err = handle_worked(&mut conn, &Options::default(), span, &mut out)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ReshapesHistory(_)));
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("warning: this range interrupts #1"), "{out}");
+        assert!(out.contains("#2 100000: STOP"), "{out}");
+    }
+
+    #[tokio::test]
+    async fn worked_back_to_back_is_quiet() {
+        let mut conn = with_events(&[
+            (EvtType::Start, "2024-05-02T09:00:00"),
+            (EvtType::Stop, "2024-05-02T10:00:00"),
+            (EvtType::Start, "2024-05-02T11:00:00"),
+        ])
+        .await;
+
+        let mut out = Vec::new();
+        let span = span("2024-05-02T10:00:00", "2024-05-02T11:00:00");
+        handle_worked(&mut conn, &Options::default(), span, &mut out)
+            .await
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(!out.contains("warning"), "{out}");
+    }
+
+    #[tokio::test]
+    async fn all_tasks_marks_pomodoros() {
+        let mut conn = with_events(&[
+            (EvtType::Start, "2024-05-02T09:00:00"),
+            (EvtType::Stop, "2024-05-02T09:25:00"),
+            (EvtType::Start, "2024-05-02T10:00:00"),
+            (EvtType::Stop, "2024-05-02T11:00:00"),
+        ])
+        .await;
+        db::record_pomodoro(&mut conn, 1).await.unwrap();
+
+        let tasks = all_tasks(&mut conn, &Options::default()).await.unwrap();
+        let pomodoros = tasks.iter().map(|task| task.pomodoro).collect::<Vec<_>>();
+        assert_eq!(pomodoros, [true, false]);
+    }
+}
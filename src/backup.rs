@@ -0,0 +1,347 @@
+//! Lossless backups of the event database.
+//!
+//! Unlike the [interchange](crate::interchange) formats, a backup keeps everything: event ids, event types,
+//! raw UTC timestamps, provisional flags, and completed pomodoros, along with the schema version of the database
+//! it came from.
+
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection as _, SqliteConnection};
+
+use crate::db::{self, EvtType, Id, InsertEvent, RetrieveEvent, MIGRATOR};
+
+/// A complete copy of the event database.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Backup {
+    /// The version of the latest migration applied to the database this came from.
+    pub schema_version: i64,
+    pub events: Vec<BackupEvent>,
+    /// The ids of the STARTs of completed pomodoros.
+    ///
+    /// Backups from before pomodoros were tracked have none.
+    #[serde(default)]
+    pub pomodoros: Vec<Id>,
+}
+
+/// A single event, exactly as stored.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupEvent {
+    pub id: Id,
+    pub evt_type: EvtType,
+    pub timestamp: DateTime<Utc>,
+    pub message: String,
+    pub provisional: bool,
+}
+
+impl From<RetrieveEvent> for BackupEvent {
+    fn from(event: RetrieveEvent) -> Self {
+        let RetrieveEvent {
+            id,
+            evt_type,
+            timestamp,
+            message,
+            provisional,
+        } = event;
+        Self {
+            id,
+            evt_type,
+            timestamp,
+            message,
+            provisional,
+        }
+    }
+}
+
+/// How to combine a backup with the events already in the database.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Keep existing events, and add those from the backup which aren't already present.
+    ///
+    /// Restored events are given new ids, as their original ids may already be in use.
+    #[default]
+    Merge,
+    /// Delete every existing event, and restore the backup with its original ids.
+    Replace,
+}
+
+/// What happened when restoring a backup.
+#[derive(Debug, Default)]
+pub struct RestoreSummary {
+    pub restored: usize,
+    /// Events which were already present when merging.
+    pub duplicates: usize,
+    /// Existing events which were deleted when replacing.
+    pub deleted: u64,
+}
+
+impl Backup {
+    /// Copy every event out of the database.
+    pub async fn dump(conn: &mut SqliteConnection) -> Result<Self, Error> {
+        let schema_version = db::schema_version(conn).await?;
+        let events = RetrieveEvent::all(conn)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        let pomodoros = db::all_pomodoros(conn).await?;
+        Ok(Self {
+            schema_version,
+            events,
+            pomodoros,
+        })
+    }
+
+    /// Read a backup from the file at `path`.
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|err| Error::Read(path.to_owned(), err))?;
+        serde_json::from_str(&contents).map_err(|err| Error::Parse(path.to_owned(), err))
+    }
+
+    /// Write this backup as JSON.
+    pub fn write(&self, out: &mut dyn Write) -> Result<(), Error> {
+        serde_json::to_writer_pretty(&mut *out, self).map_err(Error::Serialize)?;
+        writeln!(out).map_err(Error::Write)
+    }
+
+    /// Ensure that this backup was made from a schema this version of `worklog` knows about.
+    ///
+    /// Every schema version so far holds the same event fields, so any known version can be restored.
+    /// Versions without pomodoros simply restore none.
+    fn check_schema(&self) -> Result<(), Error> {
+        let known = self.schema_version == 0
+            || MIGRATOR
+                .iter()
+                .any(|migration| migration.version == self.schema_version);
+        if known {
+            Ok(())
+        } else {
+            Err(Error::IncompatibleSchema {
+                found: self.schema_version,
+                supported: MIGRATOR
+                    .iter()
+                    .map(|migration| migration.version)
+                    .max()
+                    .unwrap_or_default(),
+            })
+        }
+    }
+
+    /// Restore this backup into the database.
+    ///
+    /// This happens in a single transaction: either the whole backup is restored, or nothing changes.
+    pub async fn restore(
+        self,
+        conn: &mut SqliteConnection,
+        mode: Mode,
+    ) -> Result<RestoreSummary, Error> {
+        self.check_schema()?;
+
+        let mut tx = conn.begin().await.map_err(Error::Transaction)?;
+        let mut summary = RestoreSummary::default();
+
+        if mode == Mode::Replace {
+            summary.deleted = db::delete_all_events(&mut tx).await?;
+            // the changes which could have been undone were to the events being replaced
+            db::clear_undo(&mut tx).await?;
+        }
+
+        // merged events are given new ids, which the pomodoros must follow
+        let mut restored_ids = HashMap::new();
+        for event in self.events {
+            let BackupEvent {
+                id,
+                evt_type,
+                timestamp,
+                message,
+                provisional,
+            } = event;
+
+            let backup_id = id;
+            let id = match mode {
+                Mode::Replace => Some(id),
+                Mode::Merge => {
+                    if db::event_exists(&mut tx, evt_type, timestamp, &message).await? {
+                        summary.duplicates += 1;
+                        continue;
+                    }
+                    None
+                }
+            };
+
+            let event = InsertEvent {
+                evt_type,
+                timestamp,
+                message,
+                provisional,
+            };
+            let restored_id = db::insert_exact(&mut tx, id, event).await?;
+            restored_ids.insert(backup_id, restored_id);
+            summary.restored += 1;
+        }
+
+        // pomodoros of events which were already present are already recorded
+        for start in self.pomodoros {
+            if let Some(start) = restored_ids.get(&start) {
+                db::record_pomodoro(&mut tx, *start).await?;
+            }
+        }
+
+        tx.commit().await.map_err(Error::Transaction)?;
+        Ok(summary)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("reading {0}")]
+    Read(PathBuf, #[source] std::io::Error),
+    #[error("parsing backup from {0}")]
+    Parse(PathBuf, #[source] serde_json::Error),
+    #[error("serializing backup")]
+    Serialize(#[source] serde_json::Error),
+    #[error("writing backup")]
+    Write(#[source] std::io::Error),
+    #[error(
+        "backup has schema version {found}, which this worklog doesn't know; it supports up to {supported}"
+    )]
+    IncompatibleSchema { found: i64, supported: i64 },
+    #[error("restoring backup")]
+    Transaction(#[source] sqlx::Error),
+    #[error(transparent)]
+    Db(#[from] db::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn database() -> SqliteConnection {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        MIGRATOR.run(&mut conn).await.unwrap();
+        conn
+    }
+
+    fn event(evt_type: EvtType, hour: u32, message: &str, provisional: bool) -> InsertEvent {
+        InsertEvent {
+            evt_type,
+            timestamp: DateTime::from_timestamp(1714608000 + i64::from(hour) * 3600, 0).unwrap(),
+            message: message.into(),
+            provisional,
+        }
+    }
+
+    /// Everything about each event except its id, which merging doesn't keep.
+    async fn contents(conn: &mut SqliteConnection) -> Vec<(EvtType, DateTime<Utc>, String, bool)> {
+        RetrieveEvent::all(conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| {
+                (
+                    event.evt_type,
+                    event.timestamp,
+                    event.message,
+                    event.provisional,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unknown_schema_is_incompatible() {
+        let backup = Backup {
+            schema_version: i64::MAX,
+            events: Vec::new(),
+            pomodoros: Vec::new(),
+        };
+        assert!(matches!(
+            backup.check_schema(),
+            Err(Error::IncompatibleSchema {
+                found: i64::MAX,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn latest_schema_is_compatible() {
+        let latest = MIGRATOR.iter().map(|migration| migration.version).max();
+        let backup = Backup {
+            schema_version: latest.unwrap(),
+            events: Vec::new(),
+            pomodoros: Vec::new(),
+        };
+        assert!(backup.check_schema().is_ok());
+    }
+
+    #[test]
+    fn event_round_trip() {
+        let event = BackupEvent {
+            id: 12,
+            evt_type: EvtType::Stop,
+            timestamp: DateTime::from_timestamp(1714640400, 0).unwrap(),
+            message: "done".into(),
+            provisional: true,
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            json,
+            r#"{"id":12,"evt_type":"STOP","timestamp":"2024-05-02T09:00:00Z","message":"done","provisional":true}"#
+        );
+        assert_eq!(serde_json::from_str::<BackupEvent>(&json).unwrap(), event);
+    }
+
+    #[test]
+    fn backups_without_pomodoros() {
+        let backup: Backup = serde_json::from_str(r#"{"schema_version":0,"events":[]}"#).unwrap();
+        assert!(backup.pomodoros.is_empty());
+    }
+
+    #[tokio::test]
+    async fn dump_and_restore() {
+        let mut source = database().await;
+        for event in [
+            event(EvtType::Start, 9, "a", false),
+            event(EvtType::Stop, 10, "", false),
+            event(EvtType::Start, 11, "b", true),
+        ] {
+            db::insert_exact(&mut source, None, event).await.unwrap();
+        }
+        let expected = contents(&mut source).await;
+
+        let mut target = database().await;
+        let backup = Backup::dump(&mut source).await.unwrap();
+        let summary = backup.restore(&mut target, Mode::Merge).await.unwrap();
+        assert_eq!((summary.restored, summary.duplicates), (3, 0));
+        assert_eq!(contents(&mut target).await, expected);
+
+        // merging the same backup again adds nothing
+        let backup = Backup::dump(&mut source).await.unwrap();
+        let summary = backup.restore(&mut target, Mode::Merge).await.unwrap();
+        assert_eq!((summary.restored, summary.duplicates), (0, 3));
+        assert_eq!(contents(&mut target).await, expected);
+
+        let extra = event(EvtType::Stop, 12, "", false);
+        db::insert_exact(&mut target, None, extra).await.unwrap();
+        db::push_undo(&mut target, "stop", "revision")
+            .await
+            .unwrap();
+        let backup = Backup::dump(&mut source).await.unwrap();
+        let summary = backup.restore(&mut target, Mode::Replace).await.unwrap();
+        assert_eq!((summary.restored, summary.deleted), (3, 4));
+        assert_eq!(contents(&mut target).await, expected);
+        assert_eq!(
+            Backup::dump(&mut target).await.unwrap().events,
+            Backup::dump(&mut source).await.unwrap().events
+        );
+        assert_eq!(db::pop_undo(&mut target).await.unwrap(), None);
+    }
+}
@@ -5,14 +5,43 @@
 //
 // Any chance it gives me to explore a bunch of parser libraries is a purely incidental benefit.
 
-use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone as _};
+mod help;
+
+use std::{fmt, net::SocketAddr, path::PathBuf};
+
+use chrono::{
+    DateTime, Datelike as _, Duration, Local, NaiveDate, NaiveTime, TimeZone as _, Weekday,
+};
 use chrono_english::{Dialect, Interval};
 use peg::{error::ParseError, str::LineCol};
 use worklog::{
-    action::{Action, Event},
+    action::{
+        Action, Event, Export, Git, Import, Merge, Period, Report, ReportStyle, Restore, Span,
+        Split,
+    },
+    backup,
     db::Id,
+    interchange::Format,
 };
 
+/// Where `serve` listens unless told otherwise.
+const DEFAULT_LISTEN: &str = "127.0.0.1:7777";
+
+/// The most recent date which fell on the given weekday, including today.
+fn most_recent(weekday: Weekday) -> NaiveDate {
+    let today = Local::now().date_naive();
+    let days_back =
+        (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+    today - Duration::days(days_back.into())
+}
+
+fn local_datetime(date: NaiveDate, time: NaiveTime) -> Result<DateTime<Local>, Error> {
+    Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .ok_or(Error::InvalidTime)
+}
+
 fn no_start_message(require_message: bool, msg: &Option<String>) -> bool {
     require_message && (msg.is_none() || msg.as_ref().map(|msg| msg.is_empty()).unwrap_or_default())
 }
@@ -22,7 +51,7 @@ peg::parser! {
         rule ws() = quiet!{[' ' | '\t']}
         rule space() = quiet!{ws()+}
         rule space_then<T>(r: rule<T>) -> T
-            = quiet!{space() r:r() { r }}
+            = space() r:r() { r }
 
         // some basic components:
         // ---------------------
@@ -40,6 +69,14 @@ peg::parser! {
             = ts:time_spec() "ago"? {
                 chrono_english::parse_duration(ts).map_err(|err| Error::ParseInterval(ts.into(), err))
             }
+        // durations can't contain colons either, and stop before an "at"
+        rule duration_spec() -> &'input str
+            = quiet!{ds:$((!(":" / " at ") [' '..='~'])*) { ds.trim() }}
+            / expected!("duration")
+        rule duration() -> Result<Interval, Error>
+            = ds:duration_spec() {
+                chrono_english::parse_duration(ds).map_err(|err| Error::ParseInterval(ds.into(), err))
+            }
         // some special handling for specific time formats without the day
         rule timefragment(first: bool) -> u32
             = frag:$(['0'..='9']*<{if first {1} else {2}},2>) {
@@ -56,15 +93,44 @@ peg::parser! {
                     0
                 }
             }
-        rule civilian_time() -> Result<DateTime<Local>, Error>
+        rule civilian_time() -> Result<NaiveTime, Error>
             = h:timefragment(true) ":" m:timefragment(false) s:colon_seconds()? pm_offset:am_pm()? {
-                let naive = Local::now().date_naive().and_hms_opt(h + pm_offset.unwrap_or_default(), m, s.unwrap_or_default()).ok_or(Error::InvalidTime)?;
-                Local.from_local_datetime(&naive).earliest().ok_or(Error::InvalidTime)
+                NaiveTime::from_hms_opt(h + pm_offset.unwrap_or_default(), m, s.unwrap_or_default()).ok_or(Error::InvalidTime)
             }
-        rule military_time() -> Result<DateTime<Local>, Error>
+        rule military_time() -> Result<NaiveTime, Error>
             = h:timefragment(false) m:timefragment(false) s:timefragment(false)? {
-                let naive = Local::now().date_naive().and_hms_opt(h, m, s.unwrap_or_default()).ok_or(Error::InvalidTime)?;
-                Local.from_local_datetime(&naive).earliest().ok_or(Error::InvalidTime)
+                NaiveTime::from_hms_opt(h, m, s.unwrap_or_default()).ok_or(Error::InvalidTime)
+            }
+        rule time_of_day() -> Result<NaiveTime, Error>
+            = t:(
+                military_time() /
+                civilian_time()
+            ) { t }
+        // days which can prefix a time of day
+        rule weekday() -> Weekday
+            = "mon" "day"? { Weekday::Mon }
+            / "tue" "s"? "day"? { Weekday::Tue }
+            / "wed" "nesday"? { Weekday::Wed }
+            / "thu" "rs"? "day"? { Weekday::Thu }
+            / "fri" "day"? { Weekday::Fri }
+            / "sat" "urday"? { Weekday::Sat }
+            / "sun" "day"? { Weekday::Sun }
+        rule iso_date() -> Result<NaiveDate, Error>
+            = y:$(['0'..='9']*<4>) "-" m:$(['0'..='9']*<2>) "-" d:$(['0'..='9']*<2>) {
+                let parse = |frag: &str| frag.parse().expect("short runs of digits can be parsed into u32");
+                let year = y.parse().expect("four digit numbers can be parsed into i32");
+                NaiveDate::from_ymd_opt(year, parse(m), parse(d)).ok_or(Error::InvalidDate)
+            }
+        rule day() -> Result<NaiveDate, Error>
+            = "today" { Ok(Local::now().date_naive()) }
+            / "yesterday" { Local::now().date_naive().pred_opt().ok_or(Error::InvalidDate) }
+            / wd:weekday() { Ok(most_recent(wd)) }
+            / iso_date()
+        // a time of day, optionally on some day other than today
+        rule dated_time() -> Result<DateTime<Local>, Error>
+            = date:(d:day() space() ("at" space())? { d })? time:time_of_day() {
+                let date = date.transpose()?.unwrap_or_else(|| Local::now().date_naive());
+                local_datetime(date, time?)
             }
         rule english_date_time() -> Result<DateTime<Local>, Error>
             = ts:time_spec() {
@@ -73,10 +139,15 @@ peg::parser! {
         }
         rule datetime() -> Result<DateTime<Local>, Error>
              = dt:(
-                military_time() /
-                civilian_time() /
+                dated_time() /
                 english_date_time()
              ) { dt }
+        // either "at <datetime>", or "<day> at <time>"
+        rule at_datetime() -> Result<DateTime<Local>, Error>
+            = "at" space() dt:datetime() { dt }
+            / date:day() space() "at" space() time:time_of_day() {
+                local_datetime(date?, time?)
+            }
 
         // now build up a few higher-level constructs
         rule bare_message(require_message: bool) -> Result<BareMessage, Error>
@@ -102,7 +173,7 @@ peg::parser! {
                 }
             }
         rule absolute_message(require_message: bool) -> Result<AbsoluteMessage, Error>
-            = timestamp:datetime() msg:colon_message()? {
+            = timestamp:at_datetime() msg:colon_message()? {
                 let timestamp = timestamp?;
 
                 if no_start_message(require_message, &msg) {
@@ -112,6 +183,42 @@ peg::parser! {
                     Ok(AbsoluteMessage { timestamp, message })
                 }
             }
+        rule range_message() -> Result<RangeMessage, Error>
+            = date:(d:day() space() { d })? start:time_of_day() ws()* "-" ws()* stop:time_of_day() msg:colon_message()? {
+                let date = date.transpose()?.unwrap_or_else(|| Local::now().date_naive());
+                let start = local_datetime(date, start?)?;
+                let stop = local_datetime(date, stop?)?;
+
+                if no_start_message(true, &msg) {
+                    Err(Error::NoStartMessage)
+                } else {
+                    let message = msg.unwrap_or_default();
+                    Ok(RangeMessage { start, stop, message })
+                }
+            }
+        rule duration_message() -> Result<DurationMessage, Error>
+            = interval:duration() start:space_then(<at_datetime()>)? msg:colon_message()? {
+                let interval = interval?;
+                let start = start.transpose()?;
+
+                if no_start_message(true, &msg) {
+                    Err(Error::NoStartMessage)
+                } else {
+                    let message = msg.unwrap_or_default();
+                    Ok(DurationMessage { interval, start, message })
+                }
+            }
+
+        // a trailing "for <interval>" which actually parses as an interval
+        rule timebox() -> Interval
+            = space() "for" space() ds:$([' '..='~']+) {?
+                chrono_english::parse_duration(ds.trim()).map_err(|_| "interval")
+            }
+        rule timeboxed_message() -> TimeboxedMessage
+            = msg:$((!timebox() [' '..='~'])+) interval:timebox() {
+                let message = msg.trim().to_owned();
+                TimeboxedMessage { interval, message }
+            }
 
         // now the parsers for each CLI variant
         // note the explicit whitespace; we want at least one space after the keyword
@@ -119,6 +226,14 @@ peg::parser! {
             = "start" m:bare_message(true) {
                 Ok(Cli::Start(m?))
             }
+        rule start_interactive() -> Result<Cli, Error>
+            = "start" space() ("-i" / "--interactive") ![_] {
+                Ok(Cli::StartInteractive)
+            }
+        rule start_for() -> Result<Cli, Error>
+            = "start" m:space_then(<timeboxed_message()>) {
+                Ok(Cli::StartFor(m))
+            }
         rule stop() -> Result<Cli, Error>
             = "stop" m:bare_message(false) {
                 Ok(Cli::Stop(m?))
@@ -132,13 +247,114 @@ peg::parser! {
                 Ok(Cli::Stopped(m?))
             }
         rule started_at() -> Result<Cli, Error>
-            = "started at" m:space_then(<absolute_message(true)>) {
+            = "started" m:space_then(<absolute_message(true)>) {
                 Ok(Cli::StartedAt(m?))
             }
         rule stopped_at() -> Result<Cli, Error>
-            = "stopped at" m:space_then(<absolute_message(false)>) {
+            = "stopped" m:space_then(<absolute_message(false)>) {
                 Ok(Cli::StoppedAt(m?))
             }
+        rule worked() -> Result<Cli, Error>
+            = "worked" m:space_then(<range_message()>) {
+                Ok(Cli::Worked(m?))
+            }
+        rule logged() -> Result<Cli, Error>
+            = "logged" m:space_then(<duration_message()>) {
+                Ok(Cli::Logged(m?))
+            }
+
+        rule status() -> Result<Cli, Error>
+            = "status" {
+                Ok(Cli::Status)
+            }
+        rule autostop() -> Result<Cli, Error>
+            = "autostop" {
+                Ok(Cli::AutoStop)
+            }
+        // importing data from other time trackers
+        rule interchange_format() -> Format
+            = "timewarrior" { Format::Timewarrior }
+            / "timew" { Format::Timewarrior }
+            / "watson" { Format::Watson }
+            / "toggl" { Format::Toggl }
+            / ("ics" / "ical") { Format::Ics }
+            / "timeclock" { Format::Timeclock }
+            / "org" { Format::Org }
+        rule dry_run() = space() ("--dry-run" / "-n") ![_]
+        rule import() -> Result<Cli, Error>
+            = "import" format:space_then(<interchange_format()>) path:space_then(<$((!dry_run() [_])+)>) dry_run:$(dry_run())? {
+                Ok(Cli::Import(Import {
+                    format,
+                    path: path.trim().into(),
+                    dry_run: dry_run.is_some(),
+                }))
+            }
+        rule export() -> Result<Cli, Error>
+            = "export" format:space_then(<interchange_format()>) period:space_then(<period()>)? {
+                Ok(Cli::Export(Export {
+                    format,
+                    period: period.transpose()?,
+                }))
+            }
+        rule backup() -> Result<Cli, Error>
+            = "backup" path:space_then(<$([_]+)>)? {
+                Ok(Cli::Backup(path.map(|path| path.trim().into())))
+            }
+        rule replace() = space() "--replace" ![_]
+        rule restore() -> Result<Cli, Error>
+            = "restore" path:space_then(<$((!replace() [_])+)>) replace:$(replace())? {
+                let mode = if replace.is_some() { backup::Mode::Replace } else { backup::Mode::Merge };
+                Ok(Cli::Restore(Restore {
+                    path: path.trim().into(),
+                    mode,
+                }))
+            }
+        // git integration; `post-checkout` is run by the installed hook with git's arguments
+        rule git_command() -> Git
+            = "install-hook" "s"? { Git::InstallHooks }
+            / "uninstall-hook" "s"? { Git::UninstallHooks }
+            / "post-checkout" space() (!ws() [_])+ space() (!ws() [_])+ space() flag:$(['0' | '1']) {
+                Git::PostCheckout { branch_checkout: flag == "1" }
+            }
+        rule git() -> Result<Cli, Error>
+            = "git" command:space_then(<git_command()>) {
+                Ok(Cli::Git(command))
+            }
+        rule prompt() -> Result<Cli, Error>
+            = "prompt" format:space_then(<$([_]+)>)? {
+                Ok(Cli::Prompt(format.map(ToOwned::to_owned)))
+            }
+        rule daemon() -> Result<Cli, Error>
+            = "daemon" {
+                Ok(Cli::Daemon)
+            }
+        rule pomodoro() -> Result<Cli, Error>
+            = "pomodoro" space() "start" message:space_then(<$([_]+)>)? {
+                match message {
+                    Some(message) => Ok(Cli::Pomodoro(message.to_owned())),
+                    None => Err(Error::NoStartMessage),
+                }
+            }
+        rule watch() -> Result<Cli, Error>
+            = "watch" {
+                Ok(Cli::Watch)
+            }
+        rule tui() -> Result<Cli, Error>
+            = "tui" {
+                Ok(Cli::Tui)
+            }
+        rule serve() -> Result<Cli, Error>
+            = "serve" listen:(space() "--listen" space() listen:$([_]+) { listen })? {
+                let listen = listen.unwrap_or(DEFAULT_LISTEN);
+                listen
+                    .parse()
+                    .map(Cli::Serve)
+                    .map_err(|_| Error::InvalidListenAddress(listen.to_owned()))
+            }
+        rule doctor() -> Result<Cli, Error>
+            = ("doctor" / "fsck") {
+                Ok(Cli::Doctor)
+            }
 
         // path commands
         rule path_database() -> Result<Cli, Error>
@@ -151,16 +367,34 @@ peg::parser! {
             }
 
         // we need to be able to create reports for particular days
+        rule when_spec() -> &'input str
+            = quiet!{!("--" / "as ") ws:$((!(":" / "ago" / " as " / " --") [' '..='~'])*) { ws.trim() }}
+            / expected!("date")
         rule for_when() -> Result<NaiveDate, Error>
-            = "for"? when:time_spec() {
+            = "for"? when:when_spec() {
                 chrono_english::parse_date_string(when.trim(), Local::now(), Dialect::Us)
                     .map(|dt| dt.date_naive())
                     .map_err(|err| Error::ParseDatetime(when.into(), err))
             }
+        // reports and exports can cover more than a single day
+        rule period() -> Result<Period, Error>
+            = ("for" space())? "this" space() "week" { Ok(Period::week_of(Local::now().date_naive())) }
+            / ("for" space())? "last" space() "week" { Ok(Period::week_of(Local::now().date_naive() - Duration::weeks(1))) }
+            / date:for_when() { date.map(Period::day) }
+        // reports are either exported in some other format, or laid out in some style
+        rule report_as() -> Result<Format, ReportStyle>
+            = "as" space() ("markdown" / "md") { Err(ReportStyle::Markdown) }
+            / "as" space() format:interchange_format() { Ok(format) }
+        rule with_commits() -> Vec<PathBuf>
+            = "--with-commits" repos:(space() repo:$((!ws() [_])+) { repo.into() })+ { repos }
         rule report() -> Result<Cli, Error>
-            = "report" date:space_then(<for_when()>)? {
-                let date = date.transpose()?.unwrap_or_else(|| Local::now().date_naive());
-                Ok(Cli::Report(date))
+            = "report" period:space_then(<period()>)? report_as:space_then(<report_as()>)? repos:space_then(<with_commits()>)? {
+                let period = period.transpose()?.unwrap_or_else(|| Period::day(Local::now().date_naive()));
+                Ok(match report_as {
+                    Some(Ok(format)) => Cli::Export(Export { format, period: Some(period) }),
+                    Some(Err(style)) => Cli::Report(Report { period, style, repos: repos.unwrap_or_default() }),
+                    None => Cli::Report(Report { period, style: ReportStyle::Text, repos: repos.unwrap_or_default() }),
+                })
             }
 
         // we want to be able to list all the events for a particular date
@@ -179,9 +413,50 @@ peg::parser! {
                 Ok(Cli::EventRm(id))
             }
 
+        // tasks are named by the id of their START, as reports show them
+        rule task_id() -> Id = "#"? id:event_id() { id }
+        rule task_split() -> Result<Cli, Error>
+            = "task" space() "split" space() id:task_id() space() at:at_datetime() message:colon_message()? {
+                let message = message.filter(|message| !message.is_empty());
+                Ok(Cli::TaskSplit(Split { id, at: at?, message }))
+            }
+        rule task_merge() -> Result<Cli, Error>
+            = "task" space() "merge" space() first:task_id() space() second:task_id() message:colon_message()? {
+                let message = message.filter(|message| !message.is_empty());
+                Ok(Cli::TaskMerge(Merge { first, second, message }))
+            }
+        rule task_undo() -> Result<Cli, Error>
+            = "task" space() "undo" {
+                Ok(Cli::TaskUndo)
+            }
+
+        // help, either in general or for a particular command
+        rule help() -> Result<Cli, Error>
+            = ("help" / "--help" / "-h") topic:space_then(<message()>)? {
+                let topic = topic.unwrap_or_default();
+                match topic.split_whitespace().next() {
+                    None => Ok(Cli::Help(None)),
+                    Some(word) => help::lookup(word)
+                        .map(|cmd| Cli::Help(Some(cmd.name.to_owned())))
+                        .ok_or_else(|| Error::UnknownCommand(word.to_owned())),
+                }
+            }
+            / ![_] { Ok(Cli::Help(None)) }
+
         // catchall for better error messages
+        //
+        // This only catches words which are not known commands; a known command which fails to parse
+        // should produce a parse error pointing at the problem instead.
+        rule unknown_command() -> &'input str
+            = cmd:$((!ws() [' '..='~'])+) {?
+                if help::lookup(cmd).is_some() {
+                    Err("unknown command")
+                } else {
+                    Ok(cmd)
+                }
+            }
         rule catch_command() -> Result<Cli, Error>
-            = quiet!{cmd:$((!ws() [' '..='~'])+) message() {
+            = quiet!{cmd:unknown_command() message() {
                 Err(Error::UnknownCommand(cmd.trim().to_owned()))
             }}
 
@@ -190,15 +465,37 @@ peg::parser! {
             = c:(
                 started_at() /
                 started() /
+                start_interactive() /
+                start_for() /
                 start() /
                 stopped_at() /
                 stopped() /
                 stop() /
+                worked() /
+                logged() /
+                status() /
+                doctor() /
+                autostop() /
+                watch() /
+                tui() /
+                pomodoro() /
+                import() /
+                export() /
+                backup() /
+                restore() /
+                git() /
+                prompt() /
+                daemon() /
+                serve() /
                 path_database() /
                 path_config() /
                 report() /
                 event_rm() /
                 events_list() /
+                task_split() /
+                task_merge() /
+                task_undo() /
+                help() /
                 // note: this catchall should always be last in the command list
                 catch_command()
             ) { c }
@@ -239,44 +536,165 @@ pub struct AbsoluteMessage {
     message: String,
 }
 
+/// The specified time on the specified day
+#[cfg(test)]
+fn on_day_at(date: NaiveDate, h: u32, m: u32) -> DateTime<Local> {
+    Local
+        .from_local_datetime(&date.and_hms_opt(h, m, 0).expect("specified time is valid"))
+        .earliest()
+        .expect("specified time is unambiguous")
+}
+
+/// The specified time today
+#[cfg(test)]
+fn today_at(h: u32, m: u32) -> DateTime<Local> {
+    on_day_at(Local::now().date_naive(), h, m)
+}
+
 impl AbsoluteMessage {
     /// Create a new Absolute Message at the specified time today
     #[cfg(test)]
     fn new<'a>(h: u32, m: u32, message: impl Into<std::borrow::Cow<'a, str>>) -> Self {
         let message = message.into().into_owned();
-        let timestamp = Local
-            .from_local_datetime(
-                &Local::now()
-                    .date_naive()
-                    .and_hms_opt(h, m, 0)
-                    .expect("specified time is valid"),
-            )
-            .earliest()
-            .expect("specified time is unambiguous");
+        let timestamp = today_at(h, m);
         Self { timestamp, message }
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub struct RangeMessage {
+    start: DateTime<Local>,
+    stop: DateTime<Local>,
+    message: String,
+}
+
+impl RangeMessage {
+    /// Create a new Range Message between the specified times today
+    #[cfg(test)]
+    fn new(start: (u32, u32), stop: (u32, u32), message: &str) -> Self {
+        let start = today_at(start.0, start.1);
+        let stop = today_at(stop.0, stop.1);
+        let message = message.to_string();
+        Self {
+            start,
+            stop,
+            message,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TimeboxedMessage {
+    interval: Interval,
+    message: String,
+}
+
+impl TimeboxedMessage {
+    #[cfg(test)]
+    fn new(interval_secs: i32, message: &str) -> Self {
+        let interval = Interval::Seconds(interval_secs);
+        let message = message.to_string();
+        Self { interval, message }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DurationMessage {
+    interval: Interval,
+    /// When the span started, or `None` if it ends now.
+    start: Option<DateTime<Local>>,
+    message: String,
+}
+
+impl DurationMessage {
+    #[cfg(test)]
+    fn new(interval_secs: i32, start: Option<(u32, u32)>, message: &str) -> Self {
+        let interval = Interval::Seconds(interval_secs);
+        let start = start.map(|(h, m)| today_at(h, m));
+        let message = message.to_string();
+        Self {
+            interval,
+            start,
+            message,
+        }
+    }
+}
+
 /// This struct represents user input via the CLI.
 #[derive(Debug, PartialEq)]
 pub enum Cli {
     Start(BareMessage),
+    StartInteractive,
+    StartFor(TimeboxedMessage),
     Stop(BareMessage),
     Started(RelativeMessage),
     Stopped(RelativeMessage),
     StartedAt(AbsoluteMessage),
     StoppedAt(AbsoluteMessage),
-    Report(NaiveDate),
+    Worked(RangeMessage),
+    Logged(DurationMessage),
+    Status,
+    Doctor,
+    AutoStop,
+    Watch,
+    Tui,
+    Pomodoro(String),
+    Import(Import),
+    Export(Export),
+    Backup(Option<PathBuf>),
+    Restore(Restore),
+    Git(Git),
+    Prompt(Option<String>),
+    Daemon,
+    Serve(SocketAddr),
+    Report(Report),
     PathDatabase,
     PathConfig,
     EventsList(NaiveDate),
     EventRm(Id),
+    TaskSplit(Split),
+    TaskMerge(Merge),
+    TaskUndo,
+    Help(Option<String>),
+}
+
+/// Flags which come before the command, and apply to the command as a whole.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Flags {
+    /// Refuse to insert events which would split or truncate existing tasks, instead of asking.
+    pub strict: bool,
+    /// Insert events even if they split or truncate existing tasks, without asking.
+    ///
+    /// This overrides `strict`.
+    pub force: bool,
+}
+
+impl Flags {
+    /// Separate the flags from the rest of the arguments.
+    ///
+    /// Only arguments before the command word are flags; after it, they're part of the command, like its message.
+    pub fn extract(args: impl IntoIterator<Item = String>) -> (Self, Vec<String>) {
+        let mut flags = Self::default();
+        let mut args = args.into_iter().peekable();
+        while let Some(arg) = args.peek() {
+            match arg.as_str() {
+                "--strict" => flags.strict = true,
+                "--force" => flags.force = true,
+                _ => break,
+            }
+            args.next();
+        }
+        (flags, args.collect())
+    }
 }
 
 impl Cli {
     pub fn parse(input: &str) -> Result<Self, Error> {
         cli_parser::cli(input)
-            .map_err(Error::UnexpectedParseError)
+            .map_err(|error| Error::UnexpectedParseError {
+                input: input.to_owned(),
+                error,
+            })
             .and_then(std::convert::identity)
     }
 }
@@ -313,25 +731,109 @@ impl From<AbsoluteMessage> for Event {
     }
 }
 
+impl From<RangeMessage> for Span {
+    fn from(
+        RangeMessage {
+            start,
+            stop,
+            message,
+        }: RangeMessage,
+    ) -> Self {
+        Span {
+            start,
+            stop,
+            message,
+        }
+    }
+}
+
+impl From<TimeboxedMessage> for Span {
+    fn from(TimeboxedMessage { interval, message }: TimeboxedMessage) -> Self {
+        let start = Local::now();
+        Span {
+            start,
+            stop: start + interval2duration(interval),
+            message,
+        }
+    }
+}
+
+impl From<DurationMessage> for Span {
+    fn from(
+        DurationMessage {
+            interval,
+            start,
+            message,
+        }: DurationMessage,
+    ) -> Self {
+        let duration = interval2duration(interval);
+        let start = start.unwrap_or_else(|| Local::now() - duration);
+        Span {
+            start,
+            stop: start + duration,
+            message,
+        }
+    }
+}
+
 impl From<Cli> for Action {
     fn from(cli: Cli) -> Self {
         match cli {
             Cli::Start(msg) => Action::Start(msg.into()),
+            Cli::StartInteractive => Action::StartInteractive,
+            Cli::StartFor(msg) => Action::Timebox(msg.into()),
             Cli::Stop(msg) => Action::Stop(msg.into()),
             Cli::Started(msg) => Action::Start(msg.into()),
             Cli::Stopped(msg) => Action::Stop(msg.into()),
             Cli::StartedAt(msg) => Action::Start(msg.into()),
             Cli::StoppedAt(msg) => Action::Stop(msg.into()),
+            Cli::Worked(msg) => Action::Worked(msg.into()),
+            Cli::Logged(msg) => Action::Worked(msg.into()),
+            Cli::Status => Action::Status,
+            Cli::Doctor => Action::Doctor,
+            Cli::AutoStop => Action::AutoStop,
+            Cli::Import(import) => Action::Import(import),
+            Cli::Export(export) => Action::Export(export),
+            Cli::Backup(path) => Action::Backup(path),
+            Cli::Restore(restore) => Action::Restore(restore),
+            Cli::Git(git) => Action::Git(git),
+            Cli::Prompt(format) => Action::Prompt(format),
+            Cli::Watch => Action::Watch,
+            Cli::Tui => Action::Tui,
+            Cli::Pomodoro(message) => Action::Pomodoro(message),
+            Cli::Daemon => Action::Daemon,
+            Cli::Serve(listen) => Action::Serve(listen),
             Cli::PathDatabase => Action::PathDatabase,
             Cli::PathConfig => Action::PathConfig,
-            Cli::Report(date) => Action::Report(date),
+            Cli::Report(report) => Action::Report(report),
             Cli::EventsList(date) => Action::EventsList(date),
             Cli::EventRm(id) => Action::EventRm(id),
+            Cli::TaskSplit(split) => Action::TaskSplit(split),
+            Cli::TaskMerge(merge) => Action::TaskMerge(merge),
+            Cli::TaskUndo => Action::TaskUndo,
+            Cli::Help(topic) => Action::Help(help::render(topic.as_deref().and_then(help::lookup))),
         }
     }
 }
 
+/// Displays the input which failed to parse, with a caret under the offending column.
+struct Caret<'a> {
+    input: &'a str,
+    error: &'a ParseError<LineCol>,
+}
+
+impl fmt::Display for Caret<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { input, error } = self;
+        let indent = " ".repeat(error.location.column.saturating_sub(1));
+        let expected = &error.expected;
+        writeln!(f, "    {input}")?;
+        write!(f, "    {indent}^ expected {expected}")
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     #[error("parsing human interval from \"{0}\"")]
     ParseInterval(String, #[source] chrono_english::DateError),
@@ -339,12 +841,19 @@ pub enum Error {
     ParseDatetime(String, #[source] chrono_english::DateError),
     #[error("message is required for start variants")]
     NoStartMessage,
-    #[error("unknown command: \"{0}\"")]
+    #[error("unknown command: \"{0}\"{suggestion}", suggestion = help::DidYouMean(.0))]
     UnknownCommand(String),
-    #[error("parsing cli arguments")]
-    UnexpectedParseError(#[source] ParseError<LineCol>),
+    #[error("parsing cli arguments\n{}", Caret { input, error })]
+    UnexpectedParseError {
+        input: String,
+        error: ParseError<LineCol>,
+    },
     #[error("invalid time")]
     InvalidTime,
+    #[error("invalid date")]
+    InvalidDate,
+    #[error("invalid address to listen on: \"{0}\"; expected an ip address and port")]
+    InvalidListenAddress(String),
 }
 
 impl PartialEq for Error {
@@ -354,8 +863,9 @@ impl PartialEq for Error {
 }
 
 #[cfg(test)]
+#[allow(deprecated, clippy::zero_prefixed_literal)]
 mod example_tests {
-    use chrono::{TimeZone, Timelike};
+    use chrono::Timelike;
 
     use super::*;
 
@@ -371,16 +881,288 @@ mod example_tests {
         };
     }
 
+    #[test]
+    fn flags_before_command() {
+        let args = ["--strict", "--force", "started", "at", "0900:", "foo"].map(String::from);
+        let (flags, rest) = Flags::extract(args);
+        assert_eq!(
+            flags,
+            Flags {
+                strict: true,
+                force: true
+            }
+        );
+        assert_eq!(rest.join(" "), "started at 0900: foo");
+    }
+
+    #[test]
+    fn flags_after_command_are_message() {
+        let args = ["start", "fix", "--force", "flag"].map(String::from);
+        let (flags, rest) = Flags::extract(args);
+        assert_eq!(flags, Flags::default());
+        assert_eq!(rest.join(" "), "start fix --force flag");
+    }
+
     #[test]
     fn glorb() {
         expect_bad!("glorb" => Error::UnknownCommand(_));
     }
 
+    #[test]
+    fn strat_suggests_start() {
+        let err = Cli::parse("strat #12").unwrap_err();
+        assert!(err.to_string().contains("did you mean \"start\""));
+    }
+
+    #[test]
+    fn help_bare() {
+        expect_ok("help", Cli::Help(None));
+    }
+
+    #[test]
+    fn help_empty() {
+        expect_ok("", Cli::Help(None));
+    }
+
+    #[test]
+    fn help_started() {
+        expect_ok("help started", Cli::Help(Some("started".into())));
+    }
+
+    #[test]
+    fn help_alias() {
+        expect_ok("help event", Cli::Help(Some("events".into())));
+    }
+
+    #[test]
+    fn help_unknown() {
+        expect_bad!("help glorb" => Error::UnknownCommand(_));
+    }
+
+    #[test]
+    fn malformed_known_command_points_at_problem() {
+        let err = Cli::parse("event rm abc").unwrap_err();
+        assert!(matches!(err, Error::UnexpectedParseError { .. }));
+        let rendered = err.to_string();
+        let mut lines = rendered.lines().skip(1);
+        let input = lines.next().unwrap();
+        let caret = lines.next().unwrap();
+        assert_eq!(input.find("abc"), caret.find('^'));
+    }
+
     #[test]
     fn start_1234() {
         expect_ok("start #1234", Cli::Start(BareMessage::new("#1234")));
     }
 
+    #[test]
+    fn start_interactive() {
+        expect_ok("start -i", Cli::StartInteractive);
+        expect_ok("start --interactive", Cli::StartInteractive);
+    }
+
+    #[test]
+    fn start_message_like_a_flag() {
+        expect_ok("start -ish", Cli::Start(BareMessage::new("-ish")));
+    }
+
+    #[test]
+    fn start_12_for_30m() {
+        expect_ok(
+            "start #12 for 30m",
+            Cli::StartFor(TimeboxedMessage::new(30 * 60, "#12")),
+        );
+    }
+
+    #[test]
+    fn start_for_uses_last_for() {
+        expect_ok(
+            "start pairing for alice for 1h",
+            Cli::StartFor(TimeboxedMessage::new(60 * 60, "pairing for alice")),
+        );
+    }
+
+    #[test]
+    fn start_for_non_interval() {
+        expect_ok(
+            "start looking for bugs",
+            Cli::Start(BareMessage::new("looking for bugs")),
+        );
+    }
+
+    #[test]
+    fn status() {
+        expect_ok("status", Cli::Status);
+    }
+
+    #[test]
+    fn fsck() {
+        expect_ok("fsck", Cli::Doctor);
+    }
+
+    #[test]
+    fn import_timewarrior() {
+        expect_ok(
+            "import timewarrior /home/me/.timewarrior/data",
+            Cli::Import(Import {
+                format: Format::Timewarrior,
+                path: "/home/me/.timewarrior/data".into(),
+                dry_run: false,
+            }),
+        );
+    }
+
+    #[test]
+    fn import_timewarrior_dry_run() {
+        expect_ok(
+            "import timew data --dry-run",
+            Cli::Import(Import {
+                format: Format::Timewarrior,
+                path: "data".into(),
+                dry_run: true,
+            }),
+        );
+    }
+
+    #[test]
+    fn import_watson() {
+        expect_ok(
+            "import watson frames",
+            Cli::Import(Import {
+                format: Format::Watson,
+                path: "frames".into(),
+                dry_run: false,
+            }),
+        );
+    }
+
+    #[test]
+    fn export_toggl() {
+        expect_ok(
+            "export toggl",
+            Cli::Export(Export {
+                format: Format::Toggl,
+                period: None,
+            }),
+        );
+    }
+
+    #[test]
+    fn export_watson_for_date() {
+        expect_ok(
+            "export watson for 2024-05-02",
+            Cli::Export(Export {
+                format: Format::Watson,
+                period: NaiveDate::from_ymd_opt(2024, 5, 2).map(Period::day),
+            }),
+        );
+    }
+
+    #[test]
+    fn backup_to_stdout() {
+        expect_ok("backup", Cli::Backup(None));
+    }
+
+    #[test]
+    fn restore_replace() {
+        expect_ok(
+            "restore backup.json --replace",
+            Cli::Restore(Restore {
+                path: "backup.json".into(),
+                mode: backup::Mode::Replace,
+            }),
+        );
+    }
+
+    #[test]
+    fn export_timeclock_this_week() {
+        expect_ok(
+            "export timeclock for this week",
+            Cli::Export(Export {
+                format: Format::Timeclock,
+                period: Some(Period::week_of(Local::now().date_naive())),
+            }),
+        );
+    }
+
+    #[test]
+    fn import_org() {
+        expect_ok(
+            "import org ~/notes/work.org -n",
+            Cli::Import(Import {
+                format: Format::Org,
+                path: "~/notes/work.org".into(),
+                dry_run: true,
+            }),
+        );
+    }
+
+    #[test]
+    fn git_install_hooks() {
+        expect_ok("git install-hooks", Cli::Git(Git::InstallHooks));
+    }
+
+    #[test]
+    fn git_post_checkout() {
+        expect_ok(
+            "git post-checkout 4b825dc 9f3e2a1 1",
+            Cli::Git(Git::PostCheckout {
+                branch_checkout: true,
+            }),
+        );
+    }
+
+    #[test]
+    fn prompt_with_format() {
+        expect_ok(
+            "prompt {message} ({elapsed})",
+            Cli::Prompt(Some("{message} ({elapsed})".into())),
+        );
+    }
+
+    #[test]
+    fn daemon() {
+        expect_ok("daemon", Cli::Daemon);
+    }
+
+    #[test]
+    fn watch() {
+        expect_ok("watch", Cli::Watch);
+    }
+
+    #[test]
+    fn tui() {
+        expect_ok("tui", Cli::Tui);
+    }
+
+    #[test]
+    fn pomodoro_start() {
+        expect_ok("pomodoro start #1234", Cli::Pomodoro("#1234".into()));
+    }
+
+    #[test]
+    fn pomodoro_without_message() {
+        expect_bad!("pomodoro start" => Error::NoStartMessage);
+    }
+
+    #[test]
+    fn serve_default() {
+        expect_ok("serve", Cli::Serve(DEFAULT_LISTEN.parse().unwrap()));
+    }
+
+    #[test]
+    fn serve_listen() {
+        expect_ok(
+            "serve --listen 0.0.0.0:8080",
+            Cli::Serve("0.0.0.0:8080".parse().unwrap()),
+        );
+    }
+
+    #[test]
+    fn serve_bad_listen() {
+        expect_bad!("serve --listen localhost" => Error::InvalidListenAddress(_));
+    }
+
     #[test]
     fn bare_start() {
         expect_bad!("start" => Error::NoStartMessage);
@@ -481,21 +1263,154 @@ mod example_tests {
         expect_bad!("started at 2403: 3452" => Error::InvalidTime);
     }
 
+    fn yesterday() -> NaiveDate {
+        Local::now().date_naive().pred_opt().unwrap()
+    }
+
+    #[test]
+    fn started_yesterday_at_1400() {
+        expect_ok(
+            "started yesterday at 1400: backfill",
+            Cli::StartedAt(AbsoluteMessage {
+                timestamp: on_day_at(yesterday(), 14, 0),
+                message: "backfill".into(),
+            }),
+        );
+    }
+
+    #[test]
+    fn started_at_yesterday_1400() {
+        expect_ok(
+            "started at yesterday 1400: backfill",
+            Cli::StartedAt(AbsoluteMessage {
+                timestamp: on_day_at(yesterday(), 14, 0),
+                message: "backfill".into(),
+            }),
+        );
+    }
+
+    #[test]
+    fn started_mon_at_0930() {
+        let timestamp = on_day_at(most_recent(Weekday::Mon), 9, 30);
+        assert_eq!(timestamp.weekday(), Weekday::Mon);
+        expect_ok(
+            "started mon at 0930: weekly",
+            Cli::StartedAt(AbsoluteMessage {
+                timestamp,
+                message: "weekly".into(),
+            }),
+        );
+    }
+
+    #[test]
+    fn started_2024_05_02_at_civilian() {
+        expect_ok(
+            "started 2024-05-02 at 9:15am: dated",
+            Cli::StartedAt(AbsoluteMessage {
+                timestamp: on_day_at(NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(), 9, 15),
+                message: "dated".into(),
+            }),
+        );
+    }
+
+    #[test]
+    fn stopped_at_2024_05_02_1700() {
+        expect_ok(
+            "stopped at 2024-05-02 1700",
+            Cli::StoppedAt(AbsoluteMessage {
+                timestamp: on_day_at(NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(), 17, 0),
+                message: String::new(),
+            }),
+        );
+    }
+
+    #[test]
+    fn started_at_invalid_date() {
+        expect_bad!("started at 2024-13-02 0900: nope" => Error::InvalidDate);
+    }
+
+    #[test]
+    fn worked_yesterday_range() {
+        expect_ok(
+            "worked yesterday 0900-1030: meeting",
+            Cli::Worked(RangeMessage {
+                start: on_day_at(yesterday(), 9, 0),
+                stop: on_day_at(yesterday(), 10, 30),
+                message: "meeting".into(),
+            }),
+        );
+    }
+
+    #[test]
+    fn logged_at_yesterday() {
+        expect_ok(
+            "logged 1h at yesterday 14:00: review",
+            Cli::Logged(DurationMessage {
+                interval: Interval::Seconds(60 * 60),
+                start: Some(on_day_at(yesterday(), 14, 0)),
+                message: "review".into(),
+            }),
+        );
+    }
+
+    #[test]
+    fn worked_0900_1030_meeting() {
+        expect_ok(
+            "worked 0900-1030: meeting",
+            Cli::Worked(RangeMessage::new((9, 0), (10, 30), "meeting")),
+        );
+    }
+
+    #[test]
+    fn worked_civilian_range() {
+        expect_ok(
+            "worked 1:00pm - 2:15pm: #1234",
+            Cli::Worked(RangeMessage::new((13, 0), (14, 15), "#1234")),
+        );
+    }
+
+    #[test]
+    fn worked_without_message() {
+        expect_bad!("worked 0900-1030" => Error::NoStartMessage);
+    }
+
+    #[test]
+    fn logged_45m_at_1400() {
+        expect_ok(
+            "logged 45m at 14:00: review",
+            Cli::Logged(DurationMessage::new(45 * 60, Some((14, 0)), "review")),
+        );
+    }
+
+    #[test]
+    fn logged_45m() {
+        expect_ok(
+            "logged 45m: review",
+            Cli::Logged(DurationMessage::new(45 * 60, None, "review")),
+        );
+    }
+
     #[test]
     fn report_bare() {
-        expect_ok("report", Cli::Report(Local::now().date_naive()))
+        expect_ok(
+            "report",
+            Cli::Report(Period::day(Local::now().date_naive()).into()),
+        )
     }
 
     #[test]
     fn report_today() {
-        expect_ok("report today", Cli::Report(Local::now().date_naive()))
+        expect_ok(
+            "report today",
+            Cli::Report(Period::day(Local::now().date_naive()).into()),
+        )
     }
 
     #[test]
     fn report_yesterday() {
         expect_ok(
             "report yesterday",
-            Cli::Report(Local::now().date_naive().pred()),
+            Cli::Report(Period::day(Local::now().date_naive().pred()).into()),
         )
     }
 
@@ -503,10 +1418,68 @@ mod example_tests {
     fn report_2022_07_04() {
         expect_ok(
             "report 2022-07-04",
-            Cli::Report(chrono::NaiveDate::from_ymd(2022, 07, 04)),
+            Cli::Report(Period::day(chrono::NaiveDate::from_ymd(2022, 07, 04)).into()),
         )
     }
 
+    #[test]
+    fn report_this_week() {
+        expect_ok(
+            "report this week",
+            Cli::Report(Period::week_of(Local::now().date_naive()).into()),
+        );
+    }
+
+    #[test]
+    fn report_with_commits() {
+        expect_ok(
+            "report yesterday --with-commits ~/src/worklog .",
+            Cli::Report(Report {
+                period: Period::day(Local::now().date_naive().pred_opt().unwrap()),
+                style: ReportStyle::Text,
+                repos: vec!["~/src/worklog".into(), ".".into()],
+            }),
+        );
+    }
+
+    #[test]
+    fn report_as_markdown_with_commits() {
+        expect_ok(
+            "report as markdown --with-commits .",
+            Cli::Report(Report {
+                period: Period::day(Local::now().date_naive()),
+                style: ReportStyle::Markdown,
+                repos: vec![".".into()],
+            }),
+        );
+    }
+
+    #[test]
+    fn report_last_week_as_ics() {
+        expect_ok(
+            "report for last week as ics",
+            Cli::Export(Export {
+                format: Format::Ics,
+                period: Some(Period::week_of(
+                    Local::now().date_naive() - Duration::weeks(1),
+                )),
+            }),
+        );
+    }
+
+    #[test]
+    fn report_date_as_ics() {
+        expect_ok(
+            "report 2022-07-04 as ics",
+            Cli::Export(Export {
+                format: Format::Ics,
+                period: Some(Period::day(
+                    chrono::NaiveDate::from_ymd_opt(2022, 7, 4).unwrap(),
+                )),
+            }),
+        );
+    }
+
     #[test]
     fn events_bare() {
         expect_ok("events", Cli::EventsList(Local::now().date_naive()))
@@ -601,4 +1574,54 @@ mod example_tests {
     fn events_delete_1() {
         expect_ok("events delete 1", Cli::EventRm(1))
     }
+
+    #[test]
+    fn task_split_at_1415() {
+        expect_ok(
+            "task split 12 at 1415: review",
+            Cli::TaskSplit(Split {
+                id: 12,
+                at: today_at(14, 15),
+                message: Some("review".into()),
+            }),
+        );
+    }
+
+    #[test]
+    fn task_split_yesterday_keeps_message() {
+        let yesterday = Local::now().date_naive().pred_opt().unwrap();
+        expect_ok(
+            "task split #12 at yesterday 0930",
+            Cli::TaskSplit(Split {
+                id: 12,
+                at: on_day_at(yesterday, 9, 30),
+                message: None,
+            }),
+        );
+    }
+
+    #[test]
+    fn task_merge() {
+        expect_ok(
+            "task merge #12 #13",
+            Cli::TaskMerge(Merge {
+                first: 12,
+                second: 13,
+                message: None,
+            }),
+        );
+        expect_ok(
+            "task merge 12 13: pairing",
+            Cli::TaskMerge(Merge {
+                first: 12,
+                second: 13,
+                message: Some("pairing".into()),
+            }),
+        );
+    }
+
+    #[test]
+    fn task_undo() {
+        expect_ok("task undo", Cli::TaskUndo);
+    }
 }
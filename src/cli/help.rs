@@ -0,0 +1,317 @@
+//! Help text for the CLI, and guesses at what the user might have meant.
+
+use std::fmt;
+
+/// Documentation for a single top-level command.
+pub struct CommandHelp {
+    /// Canonical name of the command.
+    pub name: &'static str,
+    /// Other first words which select the same command.
+    pub aliases: &'static [&'static str],
+    /// One-line description of the command.
+    pub summary: &'static str,
+    /// Usage forms, not including the leading `worklog`.
+    pub usage: &'static [&'static str],
+}
+
+pub const COMMANDS: &[CommandHelp] = &[
+    CommandHelp {
+        name: "start",
+        aliases: &[],
+        summary: "start working on a task now",
+        usage: &[
+            "start <message>",
+            "start <message> for <interval>",
+            "start -i | --interactive",
+        ],
+    },
+    CommandHelp {
+        name: "started",
+        aliases: &[],
+        summary: "start working on a task at some point in the past",
+        usage: &[
+            "started <interval> ago: <message>",
+            "started at [day] <time>: <message>",
+            "started <day> at <time>: <message>",
+        ],
+    },
+    CommandHelp {
+        name: "stop",
+        aliases: &[],
+        summary: "stop working on the current task now",
+        usage: &["stop [message]"],
+    },
+    CommandHelp {
+        name: "stopped",
+        aliases: &[],
+        summary: "stop working on the current task at some point in the past",
+        usage: &[
+            "stopped <interval> ago[: message]",
+            "stopped at [day] <time>[: message]",
+            "stopped <day> at <time>[: message]",
+        ],
+    },
+    CommandHelp {
+        name: "worked",
+        aliases: &[],
+        summary: "log a task which started and stopped at particular times",
+        usage: &["worked [day] <time>-<time>: <message>"],
+    },
+    CommandHelp {
+        name: "logged",
+        aliases: &[],
+        summary: "log a task which took a particular amount of time",
+        usage: &[
+            "logged <interval>: <message>",
+            "logged <interval> at [day] <time>: <message>",
+        ],
+    },
+    CommandHelp {
+        name: "status",
+        aliases: &[],
+        summary: "show the task currently in progress",
+        usage: &["status"],
+    },
+    CommandHelp {
+        name: "report",
+        aliases: &[],
+        summary: "list the tasks worked on during a day or week, or export them",
+        usage: &[
+            "report [for] [date]",
+            "report [for] (this|last) week",
+            "report [for] <date or week> [as markdown] [--with-commits <repo>...]",
+            "report [for] <date or week> as <export format>",
+        ],
+    },
+    CommandHelp {
+        name: "events",
+        aliases: &["event"],
+        summary: "list or remove the raw events making up the log",
+        usage: &["events [list] [for] [date]", "event rm <id>"],
+    },
+    CommandHelp {
+        name: "task",
+        aliases: &[],
+        summary: "split a task in two, or merge two adjacent tasks, undoably",
+        usage: &[
+            "task split <id> at <time>[: <message>]",
+            "task merge <id> <id>[: <message>]",
+            "task undo",
+        ],
+    },
+    CommandHelp {
+        name: "import",
+        aliases: &[],
+        summary: "import intervals from another time tracker",
+        usage: &[
+            "import timewarrior <data dir> [--dry-run]",
+            "import watson <frames file> [--dry-run]",
+            "import toggl <csv file> [--dry-run]",
+            "import org <org file> [--dry-run]",
+        ],
+    },
+    CommandHelp {
+        name: "export",
+        aliases: &[],
+        summary: "write tasks to stdout in another time tracker's format",
+        usage: &["export (watson|toggl|ics|timeclock|org) [for <date or week>]"],
+    },
+    CommandHelp {
+        name: "backup",
+        aliases: &[],
+        summary: "write a lossless JSON backup of the database to a file or stdout",
+        usage: &["backup [file]"],
+    },
+    CommandHelp {
+        name: "restore",
+        aliases: &[],
+        summary: "restore a backup, merging it with existing events or replacing them",
+        usage: &["restore <file> [--replace]"],
+    },
+    CommandHelp {
+        name: "prompt",
+        aliases: &[],
+        summary: "print the task in progress, quickly enough for a shell prompt",
+        usage: &["prompt [format]"],
+    },
+    CommandHelp {
+        name: "daemon",
+        aliases: &[],
+        summary: "keep the database open and serve other invocations over a unix socket",
+        usage: &["daemon"],
+    },
+    CommandHelp {
+        name: "serve",
+        aliases: &[],
+        summary: "serve events, reports, and status as JSON over http",
+        usage: &["serve", "serve --listen 127.0.0.1:7777"],
+    },
+    CommandHelp {
+        name: "git",
+        aliases: &[],
+        summary: "start a task whenever a branch is checked out in the current repository",
+        usage: &["git install-hooks", "git uninstall-hooks"],
+    },
+    CommandHelp {
+        name: "doctor",
+        aliases: &["fsck"],
+        summary: "find anomalies in the event history, and offer to fix them",
+        usage: &["doctor"],
+    },
+    CommandHelp {
+        name: "autostop",
+        aliases: &[],
+        summary: "log the stops implied by the configured workday for every runaway task",
+        usage: &["autostop"],
+    },
+    CommandHelp {
+        name: "pomodoro",
+        aliases: &[],
+        summary: "work on a task for a pomodoro, then take a break",
+        usage: &["pomodoro start <message>"],
+    },
+    CommandHelp {
+        name: "watch",
+        aliases: &[],
+        summary: "notice idle time, and ask whether to keep, discard, or reassign it on return",
+        usage: &["watch"],
+    },
+    CommandHelp {
+        name: "tui",
+        aliases: &[],
+        summary: "review and correct tasks on an interactive timeline",
+        usage: &["tui"],
+    },
+    CommandHelp {
+        name: "path",
+        aliases: &["paths"],
+        summary: "show where worklog keeps its files",
+        usage: &["path db", "path config"],
+    },
+    CommandHelp {
+        name: "help",
+        aliases: &["--help", "-h"],
+        summary: "show this help, or help for a particular command",
+        usage: &["help [command]"],
+    },
+];
+
+/// Find the command selected by a particular first word.
+pub fn lookup(word: &str) -> Option<&'static CommandHelp> {
+    COMMANDS
+        .iter()
+        .find(|cmd| cmd.name == word || cmd.aliases.contains(&word))
+}
+
+/// Render general help, or help for a single command.
+pub fn render(command: Option<&CommandHelp>) -> String {
+    let mut out = String::new();
+    match command {
+        None => {
+            out.push_str("worklog: keep track of what you were working on\n\n");
+            out.push_str("Commands:\n");
+            for cmd in COMMANDS {
+                out.push_str(&format!("  {:<10} {}\n", cmd.name, cmd.summary));
+            }
+            out.push_str("\nFlags:\n");
+            out.push_str(
+                "  --strict   refuse to log events which would split or truncate existing tasks\n",
+            );
+            out.push_str(
+                "  --force    log events which split or truncate existing tasks without asking\n",
+            );
+            out.push_str("\nRun `worklog help <command>` for details on a particular command.\n");
+        }
+        Some(cmd) => {
+            out.push_str(&format!("{}: {}\n\nUsage:\n", cmd.name, cmd.summary));
+            for usage in cmd.usage {
+                out.push_str(&format!("  worklog {usage}\n"));
+            }
+            if !cmd.aliases.is_empty() {
+                out.push_str(&format!("\nAliases: {}\n", cmd.aliases.join(", ")));
+            }
+        }
+    }
+    out
+}
+
+/// Optimal string alignment distance between two strings.
+///
+/// This is Levenshtein distance which additionally counts a transposition of adjacent characters as a single edit,
+/// so that `strat` is a single edit away from `start`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // d[i][j] is the distance between the first i chars of a and the first j chars of b
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+/// Find the known command name closest to `word`, if any is plausibly close.
+pub fn suggest(word: &str) -> Option<&'static str> {
+    let threshold = word.chars().count().div_ceil(3);
+    COMMANDS
+        .iter()
+        .flat_map(|cmd| std::iter::once(&cmd.name).chain(cmd.aliases))
+        .map(|name| (edit_distance(word, name), *name))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// Displays a suggestion for an unknown command, or nothing if there is no good suggestion.
+pub struct DidYouMean<'a>(pub &'a str);
+
+impl fmt::Display for DidYouMean<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match suggest(self.0) {
+            Some(suggestion) => write!(f, "; did you mean \"{suggestion}\"?"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transposition_is_one_edit() {
+        assert_eq!(edit_distance("strat", "start"), 1);
+    }
+
+    #[test]
+    fn suggest_strat() {
+        assert_eq!(suggest("strat"), Some("start"));
+    }
+
+    #[test]
+    fn suggest_reprot() {
+        assert_eq!(suggest("reprot"), Some("report"));
+    }
+
+    #[test]
+    fn no_suggestion_for_glorb() {
+        assert_eq!(suggest("glorb"), None);
+    }
+}
@@ -0,0 +1,506 @@
+//! User configuration, read from the file at [`paths::config`].
+//!
+//! Every setting is optional; a missing config file is equivalent to an empty one.
+//! A configuration serializes to the same form it's read from, so it can be sent to the daemon.
+
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveTime};
+use chrono_english::Interval;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::paths;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    pub workday: Workday,
+    pub links: Links,
+    pub timeclock: Timeclock,
+    pub git: Git,
+    pub prompt: Prompt,
+    pub idle: Idle,
+    pub notify: Notify,
+    pub pomodoro: Pomodoro,
+    pub picker: Picker,
+}
+
+impl Config {
+    /// Load the configuration file, or the default configuration if there is no such file.
+    pub fn load() -> Result<Self, Error> {
+        let path = paths::config();
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents).map_err(Error::Parse),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(Error::Read(err)),
+        }
+    }
+}
+
+/// Working hours, and limits on how long a task can plausibly run.
+///
+/// Tasks exceeding these limits were probably not stopped when they should have been.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Workday {
+    /// When work starts each day, e.g. `"09:00"`; only used for reminders.
+    #[serde(
+        deserialize_with = "deserialize_time",
+        serialize_with = "serialize_time",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub start: Option<NaiveTime>,
+    /// Tasks still running at this time of day are treated as having stopped then, e.g. `"18:00"`.
+    #[serde(
+        deserialize_with = "deserialize_time",
+        serialize_with = "serialize_time",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub end: Option<NaiveTime>,
+    /// Tasks running longer than this are treated as having stopped after this long, e.g. `"10h"`.
+    #[serde(
+        deserialize_with = "deserialize_duration",
+        serialize_with = "serialize_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_task_length: Option<Duration>,
+}
+
+/// How to link the issues mentioned in task messages.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Links {
+    /// The GitHub organization of issue references which don't name one, like `repo#1234`.
+    pub default_org: Option<String>,
+    /// The GitHub repository of issue references which don't name one, like `#1234`.
+    pub default_repo: Option<String>,
+}
+
+impl Links {
+    /// The links in a message, in the order they appear.
+    ///
+    /// These are URLs, things enclosed in angle brackets, and GitHub issue references like `org/repo#1234`.
+    /// Issue references which omit the org or repo are only linked if the defaults are configured.
+    pub fn links(&self, message: &str) -> Vec<String> {
+        message
+            .split_whitespace()
+            .filter_map(|word| self.link(word))
+            .collect()
+    }
+
+    fn link(&self, word: &str) -> Option<String> {
+        if let Some(inner) = word
+            .trim_end_matches(|c: char| c != '>' && c.is_ascii_punctuation())
+            .strip_prefix('<')
+            .and_then(|word| word.strip_suffix('>'))
+        {
+            return Some(if inner.contains("://") {
+                inner.to_owned()
+            } else {
+                format!("https://{inner}")
+            });
+        }
+
+        let word = word.trim_end_matches(|c: char| c.is_ascii_punctuation() && c != '/');
+        if word.starts_with("https://") || word.starts_with("http://") {
+            return Some(word.to_owned());
+        }
+
+        let (repo, number) = word.split_once('#')?;
+        if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let (org, repo) = match repo.split_once('/') {
+            Some((org, repo)) => (org, repo),
+            None if repo.is_empty() => {
+                (self.default_org.as_deref()?, self.default_repo.as_deref()?)
+            }
+            None => (self.default_org.as_deref()?, repo),
+        };
+        if org.is_empty() || repo.is_empty() {
+            return None;
+        }
+        Some(format!("https://github.com/{org}/{repo}/issues/{number}"))
+    }
+}
+
+/// How to choose the account each task is clocked in to, when exporting to the timeclock format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Timeclock {
+    /// The account for tasks which nothing else applies to.
+    pub default_account: String,
+    /// Accounts for tasks whose messages contain a particular word, e.g. `"+acme" = "clients:acme"`.
+    pub accounts: BTreeMap<String, String>,
+}
+
+impl Default for Timeclock {
+    fn default() -> Self {
+        Self {
+            default_account: "work".into(),
+            accounts: BTreeMap::new(),
+        }
+    }
+}
+
+impl Timeclock {
+    /// The account for a task with this message.
+    ///
+    /// That's the mapped account of the first word with one, or else the first `+project` tag,
+    /// or else the default account.
+    pub fn account<'a>(&'a self, message: &'a str) -> &'a str {
+        let mut words = message.split_whitespace();
+        words
+            .clone()
+            .find_map(|word| self.accounts.get(word))
+            .map(String::as_str)
+            .or_else(|| words.find_map(|word| word.strip_prefix('+').filter(|tag| !tag.is_empty())))
+            .unwrap_or(&self.default_account)
+    }
+}
+
+/// How `worklog prompt` renders the task in progress; see [`State::render`](crate::state::State::render).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Prompt {
+    pub format: String,
+    /// Rendered when no task is in progress.
+    pub idle: String,
+}
+
+impl Default for Prompt {
+    fn default() -> Self {
+        Self {
+            format: "{message} ({elapsed})".into(),
+            idle: String::new(),
+        }
+    }
+}
+
+/// How `worklog watch` notices that you've gone idle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Idle {
+    /// A shell command which prints how long you've been idle, in milliseconds, e.g. `"xprintidle"`.
+    ///
+    /// Without one, logind's idle hint for the current session is used.
+    pub command: Option<String>,
+    /// How long without input counts as having gone away, e.g. `"5m"`.
+    #[serde(
+        deserialize_with = "deserialize_interval",
+        serialize_with = "serialize_interval"
+    )]
+    pub threshold: Duration,
+}
+
+impl Default for Idle {
+    fn default() -> Self {
+        Self {
+            command: None,
+            threshold: Duration::minutes(5),
+        }
+    }
+}
+
+/// When and how `worklog watch` sends desktop notifications.
+///
+/// Every notification is off unless configured.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Notify {
+    /// A command to send notifications with, instead of D-Bus, e.g. `"notify-send"`.
+    ///
+    /// It's given the summary and body as arguments.
+    pub command: Option<String>,
+    /// Remind about tasks which have run for this long, e.g. `"2h"`.
+    #[serde(
+        deserialize_with = "deserialize_duration",
+        serialize_with = "serialize_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub long_task: Option<Duration>,
+    /// During working hours, remind when nothing has been in progress for this long, e.g. `"15m"`.
+    #[serde(
+        deserialize_with = "deserialize_duration",
+        serialize_with = "serialize_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub nothing_running: Option<Duration>,
+    /// Summarize the day at the end of the workday.
+    pub end_of_day: bool,
+}
+
+/// The intervals of `worklog pomodoro`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Pomodoro {
+    /// How long to work, e.g. `"25m"`.
+    #[serde(
+        deserialize_with = "deserialize_interval",
+        serialize_with = "serialize_interval"
+    )]
+    pub work: Duration,
+    #[serde(
+        deserialize_with = "deserialize_interval",
+        serialize_with = "serialize_interval"
+    )]
+    pub short_break: Duration,
+    #[serde(
+        deserialize_with = "deserialize_interval",
+        serialize_with = "serialize_interval"
+    )]
+    pub long_break: Duration,
+    /// Every this many pomodoros in a day, take a long break instead of a short one.
+    pub long_break_every: usize,
+    /// Send a desktop notification, as configured under `[notify]`, when each interval ends.
+    pub notify: bool,
+}
+
+impl Default for Pomodoro {
+    fn default() -> Self {
+        Self {
+            work: Duration::minutes(25),
+            short_break: Duration::minutes(5),
+            long_break: Duration::minutes(15),
+            long_break_every: 4,
+            notify: false,
+        }
+    }
+}
+
+/// The messages offered by `worklog start -i`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Picker {
+    /// Offer the messages of tasks started within this long, e.g. `"30d"`.
+    #[serde(
+        deserialize_with = "deserialize_interval",
+        serialize_with = "serialize_interval"
+    )]
+    pub recent: Duration,
+}
+
+impl Default for Picker {
+    fn default() -> Self {
+        Self {
+            recent: Duration::days(30),
+        }
+    }
+}
+
+/// How to derive a task from the name of a checked-out git branch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Git {
+    /// Branches whose names don't match this don't start a task.
+    #[serde(
+        deserialize_with = "deserialize_regex",
+        serialize_with = "serialize_regex"
+    )]
+    pub branch_pattern: Regex,
+    /// The message of the task to start, with `$1` replaced by the pattern's first capture group, and so on.
+    pub message: String,
+}
+
+impl Default for Git {
+    fn default() -> Self {
+        Self {
+            branch_pattern: Regex::new(r"(\d+)").expect("default branch pattern is valid"),
+            message: "#$1".into(),
+        }
+    }
+}
+
+impl Git {
+    /// The message of the task to start for this branch, if it matches the branch pattern.
+    pub fn message_for(&self, branch: &str) -> Option<String> {
+        let captures = self.branch_pattern.captures(branch)?;
+        let mut message = String::new();
+        captures.expand(&self.message, &mut message);
+        Some(message)
+    }
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Regex::new(&s)
+        .map_err(|err| serde::de::Error::custom(format!("invalid pattern \"{s}\": {err}")))
+}
+
+fn deserialize_time<'de, D>(deserializer: D) -> Result<Option<NaiveTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    ["%H:%M", "%H%M", "%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(&s, format).ok())
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid time of day: \"{s}\"")))
+}
+
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_interval(deserializer).map(Some)
+}
+
+fn deserialize_interval<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    let interval = chrono_english::parse_duration(&s)
+        .map_err(|err| serde::de::Error::custom(format!("invalid duration \"{s}\": {err}")))?;
+    let duration = match interval {
+        Interval::Seconds(s) => Duration::seconds(s.into()),
+        Interval::Days(d) => Duration::days(d.into()),
+        Interval::Months(m) => Duration::days(Into::<i64>::into(m) * 30),
+    };
+    Ok(duration)
+}
+
+fn serialize_regex<S: Serializer>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(regex.as_str())
+}
+
+/// Only ever called on `Some`, as `None` is skipped.
+fn serialize_time<S: Serializer>(
+    time: &Option<NaiveTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => serializer.collect_str(&time.format("%H:%M:%S")),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Only ever called on `Some`, as `None` is skipped.
+fn serialize_duration<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serialize_interval(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn serialize_interval<S: Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{}s", duration.num_seconds()))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("reading config file")]
+    Read(#[source] std::io::Error),
+    #[error("parsing config file")]
+    Parse(#[source] toml::de::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configured() -> Links {
+        Links {
+            default_org: Some("org".into()),
+            default_repo: Some("repo".into()),
+        }
+    }
+
+    #[test]
+    fn issue_references() {
+        assert_eq!(
+            configured().links("#1234: fix foo#5, foo/bar#6 and #abc"),
+            [
+                "https://github.com/org/repo/issues/1234",
+                "https://github.com/org/foo/issues/5",
+                "https://github.com/foo/bar/issues/6",
+            ]
+        );
+    }
+
+    #[test]
+    fn angle_brackets_and_urls() {
+        assert_eq!(
+            Links::default().links("see <example.org>, https://example.com/notes."),
+            ["https://example.org", "https://example.com/notes"]
+        );
+    }
+
+    #[test]
+    fn timeclock_accounts() {
+        let timeclock: Timeclock = toml::from_str(
+            r#"
+            default-account = "misc"
+            [accounts]
+            "+acme" = "clients:acme"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(timeclock.account("review +worklog +acme"), "clients:acme");
+        assert_eq!(timeclock.account("review +worklog"), "worklog");
+        assert_eq!(timeclock.account("review"), "misc");
+    }
+
+    #[test]
+    fn git_branch_messages() {
+        let git = Git::default();
+        assert_eq!(
+            git.message_for("feature/1234-foo").as_deref(),
+            Some("#1234")
+        );
+        assert_eq!(git.message_for("main"), None);
+
+        let git: Git = toml::from_str(
+            r#"
+            branch-pattern = '^(\w+)/(\d+)'
+            message = "$2: $1"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(git.message_for("fix/12-parser").as_deref(), Some("12: fix"));
+    }
+
+    #[test]
+    fn references_need_defaults() {
+        assert_eq!(
+            Links::default().links("#12 foo#34 foo/bar#56"),
+            ["https://github.com/foo/bar/issues/56"]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let config: Config = toml::from_str(
+            r#"
+            [workday]
+            end = "18:00"
+            max-task-length = "10h"
+            [idle]
+            threshold = "90s"
+            [git]
+            branch-pattern = '^(\w+)/(\d+)'
+            "#,
+        )
+        .unwrap();
+
+        let json = serde_json::to_string(&config).unwrap();
+        let config: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(config.workday.start, None);
+        assert_eq!(config.workday.end, NaiveTime::from_hms_opt(18, 0, 0));
+        assert_eq!(config.workday.max_task_length, Some(Duration::hours(10)));
+        assert_eq!(config.idle.threshold, Duration::seconds(90));
+        assert_eq!(config.pomodoro.work, Duration::minutes(25));
+        assert_eq!(config.git.branch_pattern.as_str(), r"^(\w+)/(\d+)");
+    }
+}
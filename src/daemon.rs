@@ -0,0 +1,435 @@
+//! A long-running process which holds the database open, and executes actions on behalf of clients.
+//!
+//! Clients connect to the Unix socket at [`paths::socket`] and exchange newline-delimited JSON messages.
+//! Each [`Request`] line is answered by a [`Response`] line, in order. Subscribers are also sent a
+//! [`Response::Changed`] line, with the new [`State`], whenever the database changes.
+
+use std::{io, path::PathBuf, rc::Rc};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixListener, UnixStream,
+    },
+    sync::{broadcast, Mutex},
+};
+
+use crate::{
+    action::{self, Action, Options},
+    config::Config,
+    db::{self, Access, Db, Overlap},
+    paths,
+    state::{self, State},
+};
+
+/// A message from a client to the daemon.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Request {
+    /// Execute an action with the client's configuration, answered with its output.
+    Execute {
+        action: Action,
+        overlap: Overlap,
+        #[serde(default)]
+        config: Box<Config>,
+    },
+    /// Receive a [`Response::Changed`] whenever the database changes.
+    Subscribe,
+    /// The database was changed by someone else; tell the subscribers.
+    Changed,
+}
+
+/// A message from the daemon to a client.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    /// The action succeeded, and printed this.
+    Output(String),
+    /// The request failed.
+    Error {
+        message: String,
+        /// The action was refused because it would split or truncate existing tasks.
+        reshapes_history: bool,
+    },
+    /// A request which needs no answer beyond acknowledgement succeeded.
+    Ok,
+    /// The database changed, leaving this task in progress.
+    Changed(State),
+}
+
+struct Shared {
+    db: Mutex<Db>,
+    changes: broadcast::Sender<State>,
+    /// Rewrite the state file after every change, as actions do.
+    refresh_state: bool,
+}
+
+impl Shared {
+    async fn handle(&self, request: Request) -> Response {
+        match request {
+            Request::Execute {
+                action,
+                overlap,
+                config,
+            } => {
+                let config = *config;
+                self.execute(action, Options { overlap, config }).await
+            }
+            Request::Subscribe => Response::Ok,
+            Request::Changed => match self.broadcast().await {
+                Ok(()) => Response::Ok,
+                Err(err) => error_response(&err, false),
+            },
+        }
+    }
+
+    async fn execute(&self, action: Action, options: Options) -> Response {
+        if !action.delegable() {
+            return Response::Error {
+                message: "this action can't be executed by the daemon".into(),
+                reshapes_history: false,
+            };
+        }
+
+        let mutates = action.mutates();
+        let mut out = Vec::new();
+        let result = {
+            let mut db = self.db.lock().await;
+            action
+                .execute(&mut db, &options, self.refresh_state, &mut out)
+                .await
+        };
+
+        match result {
+            Ok(()) => {
+                if mutates {
+                    if let Err(err) = self.broadcast().await {
+                        eprintln!("notifying subscribers: {err}");
+                    }
+                }
+                Response::Output(String::from_utf8_lossy(&out).into_owned())
+            }
+            Err(err) => {
+                let reshapes_history = matches!(err, action::Error::ReshapesHistory(_));
+                error_response(&err, reshapes_history)
+            }
+        }
+    }
+
+    /// Tell every subscriber about the current state.
+    async fn broadcast(&self) -> Result<(), state::Error> {
+        let state = {
+            let mut db = self.db.lock().await;
+            State::query(db.conn().await?).await?
+        };
+        // it's fine for there to be no subscribers
+        let _ = self.changes.send(state);
+        Ok(())
+    }
+}
+
+/// Describe an error along with its chain of causes.
+fn error_response(err: &dyn std::error::Error, reshapes_history: bool) -> Response {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    Response::Error {
+        message,
+        reshapes_history,
+    }
+}
+
+async fn send(write: &mut OwnedWriteHalf, response: &Response) -> Result<(), Error> {
+    let mut line = serde_json::to_vec(response).map_err(Error::Serialize)?;
+    line.push(b'\n');
+    write.write_all(&line).await.map_err(Error::Io)
+}
+
+/// The next change, for subscribers; never resolves for anyone else.
+async fn next_change(changes: &mut Option<broadcast::Receiver<State>>) -> Option<State> {
+    let Some(receiver) = changes else {
+        return std::future::pending().await;
+    };
+    loop {
+        match receiver.recv().await {
+            Ok(state) => return Some(state),
+            // a slow subscriber only needs the latest state
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+async fn serve_client(stream: UnixStream, shared: Rc<Shared>) -> Result<(), Error> {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    let mut changes = None;
+
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line.map_err(Error::Io)? else {
+                    return Ok(());
+                };
+                let response = match serde_json::from_str::<Request>(&line) {
+                    Ok(Request::Subscribe) => {
+                        changes = Some(shared.changes.subscribe());
+                        Response::Ok
+                    }
+                    Ok(request) => shared.handle(request).await,
+                    Err(err) => error_response(&err, false),
+                };
+                send(&mut write, &response).await?;
+            }
+            Some(state) = next_change(&mut changes) => {
+                send(&mut write, &Response::Changed(state)).await?;
+            }
+        }
+    }
+}
+
+/// Serve clients until interrupted.
+pub async fn serve() -> Result<(), Error> {
+    let path = paths::socket();
+    if UnixStream::connect(&path).await.is_ok() {
+        return Err(Error::AlreadyRunning(path));
+    }
+    // whatever is left at the path is a socket from a daemon which didn't shut down cleanly
+    match std::fs::remove_file(&path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            return Err(Error::Bind(path, err));
+        }
+        _ => {}
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| Error::Bind(path.clone(), err))?;
+    }
+
+    let mut db = Db::lazy(Access::ReadWrite);
+    db.conn().await?;
+    let listener = UnixListener::bind(&path).map_err(|err| Error::Bind(path.clone(), err))?;
+    let shared = Rc::new(Shared {
+        db: Mutex::new(db),
+        changes: broadcast::channel(16).0,
+        refresh_state: true,
+    });
+    eprintln!("listening on {}", path.display());
+
+    // action futures borrow the database across awaits and aren't `Send`, so clients are served on this thread
+    let clients = tokio::task::LocalSet::new();
+    let result = clients
+        .run_until(async {
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => {
+                        let stream = match accepted {
+                            Ok((stream, _)) => stream,
+                            Err(err) => break Err(Error::Io(err)),
+                        };
+                        let shared = shared.clone();
+                        tokio::task::spawn_local(async move {
+                            if let Err(err) = serve_client(stream, shared).await {
+                                eprintln!("serving client: {err}");
+                            }
+                        });
+                    }
+                    _ = tokio::signal::ctrl_c() => break Ok(()),
+                }
+            }
+        })
+        .await;
+
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// A connection to a running daemon.
+pub struct Client {
+    lines: tokio::io::Lines<BufReader<OwnedReadHalf>>,
+    write: OwnedWriteHalf,
+}
+
+impl Client {
+    /// Connect to the daemon, if one is running.
+    pub async fn connect() -> Option<Self> {
+        let stream = UnixStream::connect(paths::socket()).await.ok()?;
+        Some(Self::from(stream))
+    }
+
+    /// Send a request, and wait for its response.
+    pub async fn request(&mut self, request: &Request) -> Result<Response, Error> {
+        let mut line = serde_json::to_vec(request).map_err(Error::Serialize)?;
+        line.push(b'\n');
+        self.write.write_all(&line).await.map_err(Error::Io)?;
+
+        let line = self
+            .lines
+            .next_line()
+            .await
+            .map_err(Error::Io)?
+            .ok_or(Error::Disconnected)?;
+        serde_json::from_str(&line).map_err(Error::Deserialize)
+    }
+
+    /// Have the daemon execute an action, returning what it printed.
+    pub async fn execute(&mut self, action: Action, options: &Options) -> Result<String, Error> {
+        let request = Request::Execute {
+            action,
+            overlap: options.overlap,
+            config: Box::new(options.config.clone()),
+        };
+        match self.request(&request).await? {
+            Response::Output(output) => Ok(output),
+            Response::Error {
+                message,
+                reshapes_history,
+            } => Err(Error::Remote {
+                message,
+                reshapes_history,
+            }),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// Tell the daemon that the database was changed without its involvement.
+    pub async fn notify_changed(&mut self) -> Result<(), Error> {
+        match self.request(&Request::Changed).await? {
+            Response::Ok => Ok(()),
+            Response::Error { message, .. } => Err(Error::Remote {
+                message,
+                reshapes_history: false,
+            }),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+}
+
+impl From<UnixStream> for Client {
+    fn from(stream: UnixStream) -> Self {
+        let (read, write) = stream.into_split();
+        Self {
+            lines: BufReader::new(read).lines(),
+            write,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("a daemon is already listening on {0}")]
+    AlreadyRunning(PathBuf),
+    #[error("binding to {0}")]
+    Bind(PathBuf, #[source] io::Error),
+    #[error("communicating over the daemon socket")]
+    Io(#[source] io::Error),
+    #[error("serializing a message")]
+    Serialize(#[source] serde_json::Error),
+    #[error("deserializing a message")]
+    Deserialize(#[source] serde_json::Error),
+    #[error("the daemon disconnected")]
+    Disconnected,
+    #[error("the daemon sent an unexpected response")]
+    UnexpectedResponse,
+    #[error("{message}")]
+    Remote {
+        message: String,
+        /// The daemon refused the action because it would split or truncate existing tasks.
+        reshapes_history: bool,
+    },
+    #[error(transparent)]
+    Db(#[from] db::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use sqlx::{Connection as _, SqliteConnection};
+
+    use super::*;
+    use crate::db::{EvtType, InsertEvent, RetrieveEvent, MIGRATOR};
+
+    #[test]
+    fn request_json() {
+        let request = Request::Execute {
+            action: Action::Status,
+            overlap: Overlap::Refuse,
+            config: Box::default(),
+        };
+        assert!(serde_json::to_string(&request)
+            .unwrap()
+            .starts_with(r#"{"execute":{"action":"status","overlap":"refuse","config":{"#));
+        // without a configuration, the action is executed with the default one
+        assert!(matches!(
+            serde_json::from_str(r#"{"execute":{"action":"status","overlap":"refuse"}}"#).unwrap(),
+            Request::Execute {
+                action: Action::Status,
+                overlap: Overlap::Refuse,
+                ..
+            }
+        ));
+        assert!(matches!(
+            serde_json::from_str(r#""subscribe""#).unwrap(),
+            Request::Subscribe
+        ));
+    }
+
+    #[test]
+    fn response_json() {
+        let response = Response::Output("not working on anything\n".into());
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(json, r#"{"output":"not working on anything\n"}"#);
+        assert_eq!(serde_json::from_str::<Response>(&json).unwrap(), response);
+    }
+
+    #[tokio::test]
+    async fn execute_with_client_config() {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        MIGRATOR.run(&mut conn).await.unwrap();
+        InsertEvent {
+            evt_type: EvtType::Start,
+            timestamp: Utc::now() - chrono::Duration::hours(3),
+            message: "runaway".into(),
+            provisional: false,
+        }
+        .insert(&mut conn, Overlap::Refuse)
+        .await
+        .unwrap();
+        let shared = Rc::new(Shared {
+            db: Mutex::new(conn.into()),
+            changes: broadcast::channel(16).0,
+            refresh_state: false,
+        });
+
+        let (client, server) = UnixStream::pair().unwrap();
+        let mut client = Client::from(client);
+        let local = tokio::task::LocalSet::new();
+        local.spawn_local(serve_client(server, shared.clone()));
+        let stops = || async {
+            let mut db = shared.db.lock().await;
+            RetrieveEvent::all(db.conn().await.unwrap())
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|event| matches!(event.evt_type, EvtType::Stop))
+                .count()
+        };
+        local
+            .run_until(async {
+                // without limits configured, nothing is stopped
+                let mut options = Options::default();
+                client.execute(Action::AutoStop, &options).await.unwrap();
+                assert_eq!(stops().await, 0);
+
+                options.config.workday.max_task_length = Some(chrono::Duration::hours(1));
+                client.execute(Action::AutoStop, &options).await.unwrap();
+                assert_eq!(stops().await, 1);
+            })
+            .await;
+    }
+}
@@ -1,7 +1,9 @@
 use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
 use sqlx::{
-    query, query_file_as, query_scalar,
+    migrate::Migrator,
+    query, query_file, query_file_as, query_scalar,
     sqlite::{SqliteConnectOptions, SqliteJournalMode, SqliteSynchronous},
     Connection, SqliteConnection,
 };
@@ -9,8 +11,68 @@ use sqlx::{
 pub type Id = i64;
 pub type Count = i32;
 
+/// The migrations which bring a database up to the schema this version of `worklog` expects.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// How much access something needs to the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    ReadOnly,
+    ReadWrite,
+}
+
+/// A connection to the database which is only opened once something needs it.
+///
+/// Commands which never touch the database never pay for opening it.
+pub struct Db {
+    access: Access,
+    conn: Option<SqliteConnection>,
+}
+
+impl Db {
+    /// Prepare to open the database with the given access, without opening it yet.
+    pub fn lazy(access: Access) -> Self {
+        Self { access, conn: None }
+    }
+
+    /// The connection, opening it if this is the first time it's needed.
+    pub async fn conn(&mut self) -> Result<&mut SqliteConnection, Error> {
+        if self.conn.is_none() {
+            self.conn = Some(open(self.access).await?);
+        }
+        Ok(self.conn.as_mut().expect("connection was just opened"))
+    }
+}
+
+impl From<SqliteConnection> for Db {
+    /// Use a connection which is already open.
+    fn from(conn: SqliteConnection) -> Self {
+        Self {
+            access: Access::ReadWrite,
+            conn: Some(conn),
+        }
+    }
+}
+
 pub async fn establish_connection() -> Result<SqliteConnection, Error> {
+    open(Access::ReadWrite).await
+}
+
+async fn open(access: Access) -> Result<SqliteConnection, Error> {
     let path = crate::paths::database();
+
+    if access == Access::ReadOnly && path.exists() {
+        let options = SqliteConnectOptions::new().filename(&path).read_only(true);
+        let mut connection = SqliteConnection::connect_with(&options)
+            .await
+            .map_err(Error::Connect)?;
+        if is_migrated(&mut connection).await? {
+            return Ok(connection);
+        }
+        // a read-only connection can't apply the migrations it needs, so fall through to a writable one
+        connection.close().await.map_err(Error::Connect)?;
+    }
+
     std::fs::create_dir_all(path.parent().expect("DB path is never the root"))?;
     let options = SqliteConnectOptions::new()
         .filename(&path)
@@ -21,11 +83,50 @@ pub async fn establish_connection() -> Result<SqliteConnection, Error> {
     let mut connection = SqliteConnection::connect_with(&options)
         .await
         .map_err(Error::Connect)?;
-    sqlx::migrate!().run(&mut connection).await?;
+    if !is_migrated(&mut connection).await? {
+        MIGRATOR.run(&mut connection).await?;
+        // pragma values can't be bound as parameters
+        let count = migration_count();
+        sqlx::query(&format!("PRAGMA user_version = {count}"))
+            .execute(&mut connection)
+            .await
+            .map_err(Error::SchemaVersion)?;
+    }
     Ok(connection)
 }
 
-#[derive(Clone, Copy)]
+/// Whether every migration has been applied to the database, going by its `user_version`.
+///
+/// This is much cheaper than asking the migrator, which reads and checksums every migration.
+async fn is_migrated(conn: &mut SqliteConnection) -> Result<bool, Error> {
+    let user_version: i64 = sqlx::query_scalar("PRAGMA user_version")
+        .fetch_one(conn)
+        .await
+        .map_err(Error::SchemaVersion)?;
+    Ok(usize::try_from(user_version).is_ok_and(|version| version == migration_count()))
+}
+
+/// The number of migrations; a fully-migrated database has this as its `user_version`.
+fn migration_count() -> usize {
+    MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .count()
+}
+
+/// The schema version of the connected database: the version of the latest migration applied to it.
+///
+/// This is 0 for a database to which no migrations have been applied.
+pub async fn schema_version(conn: &mut SqliteConnection) -> Result<i64, Error> {
+    // `_sqlx_migrations` is managed by sqlx itself, so it can't be checked at compile time
+    sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM _sqlx_migrations WHERE success")
+        .fetch_one(conn)
+        .await
+        .map_err(Error::SchemaVersion)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
 pub enum EvtType {
     Start,
     Stop,
@@ -67,42 +168,170 @@ impl EvtType {
     }
 }
 
+/// How to treat an insertion which would reshape existing history.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Overlap {
+    /// Refuse to insert, returning [`Error::ReshapesHistory`].
+    #[default]
+    Refuse,
+    /// Insert anyway.
+    Allow,
+}
+
+/// Find the tasks which inserting events from `from` to `to` would split or truncate.
+///
+/// Appending to the end of history never reshapes anything, so this is empty unless some real event follows `from`.
+/// Otherwise, it contains the START of the task in progress at `from`, if any, and of any task starting before `to`.
+pub async fn reshaped_by(
+    conn: &mut SqliteConnection,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<RetrieveEvent>, Error> {
+    let has_later = query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM events WHERE NOT provisional AND timestamp > ?) AS "has_later!: bool""#,
+        from
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(Error::RetrieveEvents)?;
+    if !has_later {
+        return Ok(Vec::new());
+    }
+
+    let mut reshaped: Vec<_> = in_progress_at(conn, from).await?.into_iter().collect();
+    reshaped.extend(
+        RetrieveEvent::events_between(conn, from, to)
+            .await?
+            .into_iter()
+            .filter(|event| matches!(event.evt_type, EvtType::Start) && !event.provisional),
+    );
+
+    Ok(reshaped)
+}
+
+/// The START of the task in progress at `at`, if any.
+///
+/// A task which stops exactly at `at` is over by then.
+pub async fn in_progress_at(
+    conn: &mut SqliteConnection,
+    at: DateTime<Utc>,
+) -> Result<Option<RetrieveEvent>, Error> {
+    let Some(start) = RetrieveEvent::last_before(conn, at)
+        .await?
+        .filter(|event| matches!(event.evt_type, EvtType::Start))
+    else {
+        return Ok(None);
+    };
+    let stops_by_then = RetrieveEvent::first_after(conn, start.timestamp)
+        .await?
+        .is_some_and(|next| matches!(next.evt_type, EvtType::Stop) && next.timestamp <= at);
+    Ok((!stops_by_then).then_some(start))
+}
+
 /// This type can be inserted into the Event database.
 pub struct InsertEvent {
     pub evt_type: EvtType,
     pub timestamp: DateTime<Utc>,
     pub message: String,
+    /// Provisional events are scheduled ahead of time.
+    ///
+    /// They are superseded by any later real event.
+    pub provisional: bool,
 }
 
 impl InsertEvent {
     /// Insert this event into the database, returning its id.
-    pub async fn insert(self, conn: &mut SqliteConnection) -> Result<Id, Error> {
+    ///
+    /// Unless `overlap` allows it, this refuses to insert a real event which would split or truncate
+    /// an existing task.
+    pub async fn insert(self, conn: &mut SqliteConnection, overlap: Overlap) -> Result<Id, Error> {
+        let evt_type_id = self.evt_type.id(conn).await?;
+
+        // use a transaction to force this query to finalize
+        let mut tx = conn.begin().await.map_err(Error::InsertEvent)?;
+
+        if overlap == Overlap::Refuse && !self.provisional {
+            let reshaped = reshaped_by(&mut tx, self.timestamp, self.timestamp).await?;
+            if !reshaped.is_empty() {
+                return Err(Error::ReshapesHistory(reshaped));
+            }
+        }
+
+        let id = self.insert_with_type(&mut tx, evt_type_id).await?;
+
+        // finalize the transaction
+        tx.commit().await.map_err(Error::InsertEvent)?;
+
+        Ok(id)
+    }
+
+    /// Insert this event given its already-known event type id.
+    ///
+    /// If this is a real event, it supersedes any provisional events scheduled after it.
+    ///
+    /// This does not manage any transaction; that's up to the caller.
+    async fn insert_with_type(
+        self,
+        conn: &mut SqliteConnection,
+        evt_type_id: Id,
+    ) -> Result<Id, Error> {
         let Self {
-            evt_type,
             timestamp,
             message,
+            provisional,
+            ..
         } = self;
-        let evt_type_id = evt_type.id(conn).await?;
 
-        // use a transaction to force this query to finalize
-        let mut tx = conn.begin().await.map_err(Error::InsertEvent)?;
+        if !provisional {
+            query_file!("queries/supersede_provisional.sql", timestamp)
+                .execute(&mut *conn)
+                .await
+                .map_err(Error::SupersedeProvisional)?;
+        }
 
-        let id = query!(
-            "insert into events(evt_type, timestamp, message) values (?, ?, ?) returning id",
+        query!(
+            "insert into events(evt_type, timestamp, message, provisional) values (?, ?, ?, ?) returning id",
             evt_type_id,
             timestamp,
-            message
+            message,
+            provisional,
         )
-        .fetch_one(&mut *tx)
+        .fetch_one(conn)
         .await
         .map(|row| row.id)
-        .map_err(Error::InsertEvent)?;
+        .map_err(Error::InsertEvent)
+    }
+}
 
-        // finalize the transaction
-        tx.commit().await.map_err(Error::InsertEvent)?;
+/// Insert a START and its matching STOP in a single transaction, returning their ids.
+///
+/// Either both events are inserted, or neither is.
+/// Unless `overlap` allows it, this refuses to insert a range which would split or truncate an existing task.
+pub async fn insert_range(
+    conn: &mut SqliteConnection,
+    start: InsertEvent,
+    stop: InsertEvent,
+    overlap: Overlap,
+) -> Result<(Id, Id), Error> {
+    let start_type_id = start.evt_type.id(conn).await?;
+    let stop_type_id = stop.evt_type.id(conn).await?;
 
-        Ok(id)
+    let mut tx = conn.begin().await.map_err(Error::InsertEvent)?;
+
+    if overlap == Overlap::Refuse {
+        let reshaped = reshaped_by(&mut tx, start.timestamp, stop.timestamp).await?;
+        if !reshaped.is_empty() {
+            return Err(Error::ReshapesHistory(reshaped));
+        }
     }
+
+    let start_id = start.insert_with_type(&mut tx, start_type_id).await?;
+    let stop_id = stop.insert_with_type(&mut tx, stop_type_id).await?;
+
+    tx.commit().await.map_err(Error::InsertEvent)?;
+
+    Ok((start_id, stop_id))
 }
 
 #[derive(sqlx::FromRow)]
@@ -111,16 +340,35 @@ struct RawRetrieveEvent {
     evt_type: Id,
     timestamp: NaiveDateTime,
     message: String,
+    provisional: bool,
 }
 
+#[derive(Debug)]
 pub struct RetrieveEvent {
     pub id: Id,
     pub evt_type: EvtType,
     pub timestamp: DateTime<Utc>,
     pub message: String,
+    pub provisional: bool,
 }
 
 impl RetrieveEvent {
+    fn from_raw(raw_event: RawRetrieveEvent, unmap_evt: impl Fn(Id) -> Option<EvtType>) -> Self {
+        let evt_type = unmap_evt(raw_event.evt_type).expect("only known event types appear here");
+        let timestamp = Utc
+            .from_local_datetime(&raw_event.timestamp)
+            .single()
+            .expect("roundtrip conversions to/from UTC should be unambiguous");
+
+        Self {
+            id: raw_event.id,
+            evt_type,
+            timestamp,
+            message: raw_event.message,
+            provisional: raw_event.provisional,
+        }
+    }
+
     /// Retrieve the events between `start` (inclusive) and `end` (exclusive).
     // TODO: rethink this interface, we need to handle overnight explicitly-stopped events
     pub async fn events_between(
@@ -139,23 +387,186 @@ impl RetrieveEvent {
             .await
             .map_err(Error::RetrieveEvents)?
         {
-            let evt_type =
-                unmap_evt(raw_event.evt_type).expect("only known event types appear here");
-            let timestamp = Utc
-                .from_local_datetime(&raw_event.timestamp)
-                .single()
-                .expect("roundtrip conversions to/from UTC should be unambiguous");
-
-            events.push(Self {
-                id: raw_event.id,
-                evt_type,
-                timestamp,
-                message: raw_event.message,
-            });
+            events.push(Self::from_raw(raw_event, &unmap_evt));
         }
 
         Ok(events)
     }
+
+    /// Retrieve every event in the database, ordered by timestamp.
+    pub async fn all(conn: &mut SqliteConnection) -> Result<Vec<Self>, Error> {
+        let unmap_evt = EvtType::unmap(conn).await?;
+
+        let mut events = Vec::new();
+        let mut raw_event_stream =
+            query_file_as!(RawRetrieveEvent, "queries/all_events.sql").fetch(conn);
+
+        while let Some(raw_event) = raw_event_stream
+            .try_next()
+            .await
+            .map_err(Error::RetrieveEvents)?
+        {
+            events.push(Self::from_raw(raw_event, &unmap_evt));
+        }
+
+        Ok(events)
+    }
+
+    /// Retrieve the most recent event strictly before `timestamp`, if any.
+    pub async fn last_before(
+        conn: &mut SqliteConnection,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Option<Self>, Error> {
+        let unmap_evt = EvtType::unmap(conn).await?;
+
+        query_file_as!(RawRetrieveEvent, "queries/last_before.sql", timestamp)
+            .fetch_optional(conn)
+            .await
+            .map(|maybe_raw| maybe_raw.map(|raw_event| Self::from_raw(raw_event, &unmap_evt)))
+            .map_err(Error::RetrieveEvents)
+    }
+
+    /// Retrieve the earliest event strictly after `timestamp`, if any.
+    pub async fn first_after(
+        conn: &mut SqliteConnection,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Option<Self>, Error> {
+        let unmap_evt = EvtType::unmap(conn).await?;
+
+        query_file_as!(RawRetrieveEvent, "queries/first_after.sql", timestamp)
+            .fetch_optional(conn)
+            .await
+            .map(|maybe_raw| maybe_raw.map(|raw_event| Self::from_raw(raw_event, &unmap_evt)))
+            .map_err(Error::RetrieveEvents)
+    }
+
+    /// Retrieve the event with this id, if there is one.
+    pub async fn by_id(conn: &mut SqliteConnection, id: Id) -> Result<Option<Self>, Error> {
+        let unmap_evt = EvtType::unmap(conn).await?;
+
+        query_file_as!(RawRetrieveEvent, "queries/event_by_id.sql", id)
+            .fetch_optional(conn)
+            .await
+            .map(|maybe_raw| maybe_raw.map(|raw_event| Self::from_raw(raw_event, &unmap_evt)))
+            .map_err(Error::RetrieveEvents)
+    }
+}
+
+/// Whether an event of this type already exists at exactly this timestamp with exactly this message.
+pub async fn event_exists(
+    conn: &mut SqliteConnection,
+    evt_type: EvtType,
+    timestamp: DateTime<Utc>,
+    message: &str,
+) -> Result<bool, Error> {
+    let evt_type_id = evt_type.id(conn).await?;
+    query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM events WHERE evt_type = ? AND timestamp = ? AND message = ?) AS "exists!: bool""#,
+        evt_type_id,
+        timestamp,
+        message
+    )
+    .fetch_one(conn)
+    .await
+    .map_err(Error::RetrieveEvents)
+}
+
+/// Whether an event of this type with exactly this message exists between `start` (inclusive) and `end` (exclusive).
+pub async fn event_exists_between(
+    conn: &mut SqliteConnection,
+    evt_type: EvtType,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    message: &str,
+) -> Result<bool, Error> {
+    let evt_type_id = evt_type.id(conn).await?;
+    query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM events WHERE evt_type = ? AND timestamp >= ? AND timestamp < ? AND message = ?) AS "exists!: bool""#,
+        evt_type_id,
+        start,
+        end,
+        message
+    )
+    .fetch_one(conn)
+    .await
+    .map_err(Error::RetrieveEvents)
+}
+
+/// Insert an event exactly as given, returning its id.
+///
+/// Unlike [`InsertEvent::insert`], this neither checks for reshaped history nor supersedes provisional events:
+/// it's meant for restoring events which were already consistent when they were recorded.
+/// If `id` is `None`, a new id is assigned.
+///
+/// This does not manage any transaction; that's up to the caller.
+pub async fn insert_exact(
+    conn: &mut SqliteConnection,
+    id: Option<Id>,
+    event: InsertEvent,
+) -> Result<Id, Error> {
+    let evt_type_id = event.evt_type.id(conn).await?;
+    let InsertEvent {
+        timestamp,
+        message,
+        provisional,
+        ..
+    } = event;
+
+    query!(
+        "insert into events(id, evt_type, timestamp, message, provisional) values (?, ?, ?, ?, ?) returning id",
+        id,
+        evt_type_id,
+        timestamp,
+        message,
+        provisional,
+    )
+    .fetch_one(conn)
+    .await
+    .map(|row| row.id)
+    .map_err(Error::InsertEvent)
+}
+
+/// Delete every event from the database, returning how many were deleted.
+pub async fn delete_all_events(conn: &mut SqliteConnection) -> Result<u64, Error> {
+    query!("DELETE FROM events")
+        .execute(conn)
+        .await
+        .map(|query_result| query_result.rows_affected())
+        .map_err(Error::DeleteEvent)
+}
+
+/// Changes to make to an existing event.
+///
+/// Fields which are `None` are left as they are.
+pub struct UpdateEvent {
+    pub id: Id,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub message: Option<String>,
+}
+
+impl UpdateEvent {
+    /// Apply these changes to the database.
+    ///
+    /// Return whether or not the event was updated successfully.
+    /// Normally this will only be `Ok(false)` if an unused `Id` was entered.
+    pub async fn update(self, conn: &mut SqliteConnection) -> Result<bool, Error> {
+        let Self {
+            id,
+            timestamp,
+            message,
+        } = self;
+
+        query!(
+            "UPDATE events SET timestamp = COALESCE(?, timestamp), message = COALESCE(?, message) WHERE id = ?",
+            timestamp,
+            message,
+            id
+        )
+        .execute(conn)
+        .await
+        .map(|query_result| query_result.rows_affected() != 0)
+        .map_err(Error::UpdateEvent)
+    }
 }
 
 /// Delete an event from the database.
@@ -170,6 +581,117 @@ pub async fn delete_event(conn: &mut SqliteConnection, event: Id) -> Result<bool
         .map_err(Error::DeleteEvent)
 }
 
+/// Stop a pomodoro's task at `stop`, and record the pomodoro as completed, in a single transaction.
+///
+/// The stop supersedes the one scheduled when the pomodoro started. Return the id of the stop.
+pub async fn complete_pomodoro(
+    conn: &mut SqliteConnection,
+    start: Id,
+    stop: DateTime<Utc>,
+) -> Result<Id, Error> {
+    let stop_type_id = EvtType::Stop.id(conn).await?;
+
+    let mut tx = conn.begin().await.map_err(Error::InsertEvent)?;
+    let stop_id = InsertEvent {
+        evt_type: EvtType::Stop,
+        timestamp: stop,
+        message: String::new(),
+        provisional: false,
+    }
+    .insert_with_type(&mut tx, stop_type_id)
+    .await?;
+    record_pomodoro(&mut tx, start).await?;
+    tx.commit().await.map_err(Error::InsertEvent)?;
+
+    Ok(stop_id)
+}
+
+/// Record that the task begun by the START `start` was a completed pomodoro.
+pub async fn record_pomodoro(conn: &mut SqliteConnection, start: Id) -> Result<(), Error> {
+    query!("INSERT OR IGNORE INTO pomodoros(start) VALUES (?)", start)
+        .execute(conn)
+        .await
+        .map(|_| ())
+        .map_err(Error::Pomodoros)
+}
+
+/// Whether this event is the START of a completed pomodoro.
+pub async fn is_pomodoro(conn: &mut SqliteConnection, start: Id) -> Result<bool, Error> {
+    query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM pomodoros WHERE start = ?) AS "exists!: bool""#,
+        start
+    )
+    .fetch_one(conn)
+    .await
+    .map_err(Error::Pomodoros)
+}
+
+/// The STARTs of the completed pomodoros which began from `start` (inclusive) until `end` (exclusive).
+pub async fn pomodoros_between(
+    conn: &mut SqliteConnection,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<Id>, Error> {
+    query_scalar!(
+        "SELECT pomodoros.start FROM pomodoros JOIN events ON events.id = pomodoros.start WHERE events.timestamp >= ? AND events.timestamp < ? ORDER BY events.timestamp, events.id",
+        start,
+        end
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(Error::Pomodoros)
+}
+
+/// The STARTs of every completed pomodoro.
+pub async fn all_pomodoros(conn: &mut SqliteConnection) -> Result<Vec<Id>, Error> {
+    query_scalar!("SELECT start FROM pomodoros ORDER BY start")
+        .fetch_all(conn)
+        .await
+        .map_err(Error::Pomodoros)
+}
+
+/// Remember how to undo a change, with its description and its revision serialized by the caller.
+pub async fn push_undo(
+    conn: &mut SqliteConnection,
+    description: &str,
+    revision: &str,
+) -> Result<Id, Error> {
+    query_scalar!(
+        "INSERT INTO undo(description, revision) VALUES (?, ?) RETURNING id",
+        description,
+        revision
+    )
+    .fetch_one(conn)
+    .await
+    .map_err(Error::Undo)
+}
+
+/// Forget the most recent change which could be undone, returning its description and revision.
+pub async fn pop_undo(conn: &mut SqliteConnection) -> Result<Option<(String, String)>, Error> {
+    let Some(latest) =
+        query!("SELECT id, description, revision FROM undo ORDER BY id DESC LIMIT 1")
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(Error::Undo)?
+    else {
+        return Ok(None);
+    };
+    query!("DELETE FROM undo WHERE id = ?", latest.id)
+        .execute(conn)
+        .await
+        .map_err(Error::Undo)?;
+    Ok(Some((latest.description, latest.revision)))
+}
+
+/// Forget every change which could be undone.
+pub async fn clear_undo(conn: &mut SqliteConnection) -> Result<(), Error> {
+    query!("DELETE FROM undo")
+        .execute(conn)
+        .await
+        .map(|_| ())
+        .map_err(Error::Undo)
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("creating the database parent directory")]
@@ -178,14 +700,203 @@ pub enum Error {
     Connect(#[source] sqlx::Error),
     #[error("applying migrations")]
     Migrations(#[from] sqlx::migrate::MigrateError),
+    #[error("reading the database schema version")]
+    SchemaVersion(#[source] sqlx::Error),
     #[error("getting appropriate evt_type id")]
     GetEvtId(#[source] sqlx::Error),
     #[error("inserting event")]
     InsertEvent(#[source] sqlx::Error),
+    #[error("superseding provisional events")]
+    SupersedeProvisional(#[source] sqlx::Error),
     #[error("counting events today")]
     CountEvents(#[source] sqlx::Error),
     #[error("retrieving events")]
     RetrieveEvents(#[source] sqlx::Error),
+    #[error("inserting would split or truncate existing tasks")]
+    ReshapesHistory(Vec<RetrieveEvent>),
+    #[error("updating event")]
+    UpdateEvent(#[source] sqlx::Error),
     #[error("deleting event")]
     DeleteEvent(#[source] sqlx::Error),
+    #[error("accessing pomodoros")]
+    Pomodoros(#[source] sqlx::Error),
+    #[error("accessing the undo log")]
+    Undo(#[source] sqlx::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use EvtType::{Start, Stop};
+
+    fn at(time: &str) -> DateTime<Utc> {
+        format!("2024-05-02T{time}:00Z").parse().unwrap()
+    }
+
+    fn event(evt_type: EvtType, time: &str, message: &str) -> InsertEvent {
+        InsertEvent {
+            evt_type,
+            timestamp: at(time),
+            message: message.into(),
+            provisional: false,
+        }
+    }
+
+    /// A database holding a task from 09:00 to 10:00, and another from 11:00 to 12:00.
+    async fn with_tasks() -> SqliteConnection {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        MIGRATOR.run(&mut conn).await.unwrap();
+        for (evt_type, time, message) in [
+            (Start, "09:00", "first"),
+            (Stop, "10:00", ""),
+            (Start, "11:00", "second"),
+            (Stop, "12:00", ""),
+        ] {
+            event(evt_type, time, message)
+                .insert(&mut conn, Overlap::Refuse)
+                .await
+                .unwrap();
+        }
+        conn
+    }
+
+    async fn messages(conn: &mut SqliteConnection) -> Vec<String> {
+        RetrieveEvent::all(conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| event.message)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reshaped_tasks() {
+        let mut conn = with_tasks().await;
+
+        let starts = |reshaped: Vec<RetrieveEvent>| {
+            reshaped
+                .into_iter()
+                .map(|event| event.message)
+                .collect::<Vec<_>>()
+        };
+        // inside a task
+        let reshaped = reshaped_by(&mut conn, at("09:30"), at("09:30"))
+            .await
+            .unwrap();
+        assert_eq!(starts(reshaped), ["first"]);
+        // in the gap between tasks, but running into the next
+        let reshaped = reshaped_by(&mut conn, at("10:30"), at("11:30"))
+            .await
+            .unwrap();
+        assert_eq!(starts(reshaped), ["second"]);
+        // entirely within a gap
+        let reshaped = reshaped_by(&mut conn, at("10:15"), at("10:45"))
+            .await
+            .unwrap();
+        assert!(reshaped.is_empty());
+        // after the end of history
+        let reshaped = reshaped_by(&mut conn, at("12:30"), at("12:30"))
+            .await
+            .unwrap();
+        assert!(reshaped.is_empty());
+    }
+
+    #[tokio::test]
+    async fn back_to_back_tasks() {
+        let mut conn = with_tasks().await;
+
+        // starting just as the first task stops, and stopping just as the second starts
+        let reshaped = reshaped_by(&mut conn, at("10:00"), at("11:00"))
+            .await
+            .unwrap();
+        assert!(reshaped.is_empty());
+        insert_range(
+            &mut conn,
+            event(Start, "10:00", "between"),
+            event(Stop, "11:00", ""),
+            Overlap::Refuse,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            messages(&mut conn).await,
+            ["first", "", "between", "", "second", ""]
+        );
+
+        // starting just as the last task stops
+        let reshaped = reshaped_by(&mut conn, at("12:00"), at("12:00"))
+            .await
+            .unwrap();
+        assert!(reshaped.is_empty());
+        // running on past the start of a task still truncates it
+        let reshaped = reshaped_by(&mut conn, at("10:00"), at("10:30"))
+            .await
+            .unwrap();
+        assert_eq!(reshaped.len(), 1);
+        assert_eq!(reshaped[0].message, "between");
+    }
+
+    #[tokio::test]
+    async fn refuse_reshaping_insert() {
+        let mut conn = with_tasks().await;
+
+        let err = event(Start, "09:30", "interruption")
+            .insert(&mut conn, Overlap::Refuse)
+            .await
+            .unwrap_err();
+        let Error::ReshapesHistory(reshaped) = err else {
+            panic!("expected to refuse reshaping history, got {err}");
+        };
+        assert_eq!(reshaped.len(), 1);
+        assert_eq!(reshaped[0].message, "first");
+        assert_eq!(messages(&mut conn).await, ["first", "", "second", ""]);
+
+        let err = insert_range(
+            &mut conn,
+            event(Start, "10:30", "overlong"),
+            event(Stop, "11:30", ""),
+            Overlap::Refuse,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Error::ReshapesHistory(_)));
+        assert_eq!(messages(&mut conn).await, ["first", "", "second", ""]);
+    }
+
+    #[tokio::test]
+    async fn allow_reshaping_insert() {
+        let mut conn = with_tasks().await;
+
+        event(Start, "09:30", "interruption")
+            .insert(&mut conn, Overlap::Allow)
+            .await
+            .unwrap();
+        assert_eq!(
+            messages(&mut conn).await,
+            ["first", "interruption", "", "second", ""]
+        );
+    }
+
+    #[tokio::test]
+    async fn insert_without_overlap() {
+        let mut conn = with_tasks().await;
+
+        insert_range(
+            &mut conn,
+            event(Start, "10:15", "in the gap"),
+            event(Stop, "10:45", ""),
+            Overlap::Refuse,
+        )
+        .await
+        .unwrap();
+        event(Start, "12:30", "appended")
+            .insert(&mut conn, Overlap::Refuse)
+            .await
+            .unwrap();
+        assert_eq!(
+            messages(&mut conn).await,
+            ["first", "", "in the gap", "", "second", "", "appended"]
+        );
+    }
 }
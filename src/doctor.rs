@@ -0,0 +1,291 @@
+//! Find anomalies in the event history.
+//!
+//! Months of use leave behind all sorts of oddities: doubled STARTs and STOPs, STOPs before anything started,
+//! STARTs without a message, tasks which ran all night because nobody stopped them, scheduled STOPs which
+//! no longer stop anything, and events in the future.
+
+use std::fmt;
+
+use chrono::{DateTime, Duration, Local, Utc};
+use sqlx::SqliteConnection;
+
+use crate::{
+    config::Workday,
+    db::{self, EvtType, Id, RetrieveEvent},
+};
+
+/// Tasks running longer than this are assumed to have been forgotten,
+/// unless the workday configures a maximum task length.
+pub const MAX_TASK_HOURS: i64 = 14;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Anomaly {
+    /// A STOP which follows another STOP, and so stops nothing.
+    DoubledStop { id: Id, previous: Id },
+    /// A START which follows a START with the same message, and so starts nothing new.
+    DoubledStart { id: Id, previous: Id },
+    /// A STOP before anything was ever started.
+    LeadingStop { id: Id },
+    /// A scheduled STOP which no longer stops the task it was scheduled for.
+    ProvisionalLeftover { id: Id },
+    /// A START without any message.
+    EmptyStart { id: Id },
+    /// A task which ran implausibly long.
+    LongTask {
+        id: Id,
+        start: DateTime<Utc>,
+        duration: Duration,
+    },
+    /// A real event whose timestamp has not happened yet.
+    FutureEvent { id: Id, timestamp: DateTime<Utc> },
+}
+
+impl Anomaly {
+    /// The id of the event exhibiting this anomaly.
+    pub fn id(&self) -> Id {
+        match self {
+            Self::DoubledStop { id, .. }
+            | Self::DoubledStart { id, .. }
+            | Self::LeadingStop { id }
+            | Self::ProvisionalLeftover { id }
+            | Self::EmptyStart { id }
+            | Self::LongTask { id, .. }
+            | Self::FutureEvent { id, .. } => *id,
+        }
+    }
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DoubledStop { id, previous } => {
+                write!(f, "#{id}: doubled STOP (follows STOP #{previous})")
+            }
+            Self::DoubledStart { id, previous } => {
+                write!(f, "#{id}: doubled START (repeats START #{previous})")
+            }
+            Self::LeadingStop { id } => write!(f, "#{id}: STOP before anything started"),
+            Self::ProvisionalLeftover { id } => {
+                write!(f, "#{id}: scheduled STOP which no longer stops anything")
+            }
+            Self::EmptyStart { id } => write!(f, "#{id}: START with an empty message"),
+            Self::LongTask {
+                id,
+                start,
+                duration,
+            } => {
+                let start: DateTime<Local> = (*start).into();
+                let start = start.format("%Y-%m-%d %H%M");
+                let minutes = duration.num_minutes();
+                let hours = minutes / 60;
+                let minutes = minutes % 60;
+                write!(
+                    f,
+                    "#{id}: task started {start} ran for {hours}:{minutes:02}"
+                )
+            }
+            Self::FutureEvent { id, timestamp } => {
+                let timestamp: DateTime<Local> = (*timestamp).into();
+                let timestamp = timestamp.format("%Y-%m-%d %H%M");
+                write!(f, "#{id}: event in the future ({timestamp})")
+            }
+        }
+    }
+}
+
+/// Scan the whole event history for anomalies, in timestamp order.
+pub async fn scan(
+    conn: &mut SqliteConnection,
+    workday: &Workday,
+) -> Result<Vec<Anomaly>, db::Error> {
+    let events = RetrieveEvent::all(conn).await?;
+    let now = Utc::now();
+    let max_task = workday
+        .max_task_length
+        .unwrap_or_else(|| Duration::hours(MAX_TASK_HOURS));
+
+    let mut anomalies = Vec::new();
+    let mut previous: Option<&RetrieveEvent> = None;
+    for event in &events {
+        match (previous.map(|previous| previous.evt_type), event.evt_type) {
+            // a scheduled stop which doesn't follow a start was superseded, but not removed
+            (None | Some(EvtType::Stop), EvtType::Stop) if event.provisional => {
+                anomalies.push(Anomaly::ProvisionalLeftover { id: event.id })
+            }
+            (None, EvtType::Stop) => anomalies.push(Anomaly::LeadingStop { id: event.id }),
+            (Some(EvtType::Stop), EvtType::Stop) => anomalies.push(Anomaly::DoubledStop {
+                id: event.id,
+                previous: previous.expect("previous exists in this branch").id,
+            }),
+            (Some(EvtType::Start), _) => {
+                let start = previous.expect("previous exists in this branch");
+                if matches!(event.evt_type, EvtType::Start) && event.message == start.message {
+                    anomalies.push(Anomaly::DoubledStart {
+                        id: event.id,
+                        previous: start.id,
+                    });
+                }
+                let duration = event.timestamp - start.timestamp;
+                if duration > max_task {
+                    anomalies.push(Anomaly::LongTask {
+                        id: start.id,
+                        start: start.timestamp,
+                        duration,
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        if matches!(event.evt_type, EvtType::Start) && event.message.is_empty() {
+            anomalies.push(Anomaly::EmptyStart { id: event.id });
+        }
+        if !event.provisional && event.timestamp > now {
+            anomalies.push(Anomaly::FutureEvent {
+                id: event.id,
+                timestamp: event.timestamp,
+            });
+        }
+
+        previous = Some(event);
+    }
+
+    // the final task might still be running
+    if let Some(last) = previous.filter(|last| matches!(last.evt_type, EvtType::Start)) {
+        let duration = now - last.timestamp;
+        if duration > max_task {
+            anomalies.push(Anomaly::LongTask {
+                id: last.id,
+                start: last.timestamp,
+                duration,
+            });
+        }
+    }
+
+    Ok(anomalies)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection as _;
+
+    use super::*;
+    use crate::db::{InsertEvent, MIGRATOR};
+
+    /// An in-memory database with these events, some hours ago, each an hour apart.
+    async fn with_events(events: &[(EvtType, &str, bool)]) -> SqliteConnection {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        MIGRATOR.run(&mut conn).await.unwrap();
+        let first = Utc::now() - Duration::hours(10);
+        for (hour, (evt_type, message, provisional)) in (0..).zip(events) {
+            let event = InsertEvent {
+                evt_type: *evt_type,
+                timestamp: first + Duration::hours(hour),
+                message: (*message).into(),
+                provisional: *provisional,
+            };
+            // leftovers can't be inserted normally, as they'd be superseded
+            db::insert_exact(&mut conn, None, event).await.unwrap();
+        }
+        conn
+    }
+
+    async fn anomalies(events: &[(EvtType, &str, bool)]) -> Vec<Anomaly> {
+        let mut conn = with_events(events).await;
+        scan(&mut conn, &Workday::default()).await.unwrap()
+    }
+
+    use EvtType::{Start, Stop};
+
+    #[tokio::test]
+    async fn consistent_history() {
+        let events = [(Start, "a", false), (Start, "b", false), (Stop, "", false)];
+        assert_eq!(anomalies(&events).await, []);
+    }
+
+    #[tokio::test]
+    async fn doubled_stop() {
+        let events = [(Start, "a", false), (Stop, "", false), (Stop, "", false)];
+        assert_eq!(
+            anomalies(&events).await,
+            [Anomaly::DoubledStop { id: 3, previous: 2 }]
+        );
+    }
+
+    #[tokio::test]
+    async fn doubled_start() {
+        let events = [(Start, "a", false), (Start, "a", false), (Stop, "", false)];
+        assert_eq!(
+            anomalies(&events).await,
+            [Anomaly::DoubledStart { id: 2, previous: 1 }]
+        );
+    }
+
+    #[tokio::test]
+    async fn leading_stop() {
+        let events = [(Stop, "", false), (Start, "a", false), (Stop, "", false)];
+        assert_eq!(anomalies(&events).await, [Anomaly::LeadingStop { id: 1 }]);
+    }
+
+    #[tokio::test]
+    async fn provisional_leftover() {
+        let events = [(Start, "a", false), (Stop, "", false), (Stop, "", true)];
+        assert_eq!(
+            anomalies(&events).await,
+            [Anomaly::ProvisionalLeftover { id: 3 }]
+        );
+    }
+
+    #[tokio::test]
+    async fn provisional_stop_which_stops_a_task() {
+        let events = [(Start, "a", false), (Stop, "", true)];
+        assert_eq!(anomalies(&events).await, []);
+    }
+
+    #[tokio::test]
+    async fn empty_start() {
+        let events = [(Start, "", false), (Stop, "", false)];
+        assert_eq!(anomalies(&events).await, [Anomaly::EmptyStart { id: 1 }]);
+    }
+
+    #[tokio::test]
+    async fn long_task() {
+        let mut conn = with_events(&[(Start, "a", false), (Stop, "", false)]).await;
+        let workday = Workday {
+            max_task_length: Some(Duration::minutes(30)),
+            ..Workday::default()
+        };
+        let anomalies = scan(&mut conn, &workday).await.unwrap();
+        assert!(matches!(
+            anomalies[..],
+            [Anomaly::LongTask { id: 1, duration, .. }] if duration == Duration::hours(1)
+        ));
+    }
+
+    #[tokio::test]
+    async fn future_event() {
+        let mut conn = with_events(&[(Start, "a", false), (Stop, "", false)]).await;
+        let later = Utc::now() + Duration::hours(1);
+        for (evt_type, timestamp, message, provisional) in [
+            (Start, later, "b", false),
+            // scheduled STOPs are meant to be in the future
+            (Stop, later + Duration::hours(1), "", true),
+        ] {
+            let event = InsertEvent {
+                evt_type,
+                timestamp,
+                message: message.into(),
+                provisional,
+            };
+            db::insert_exact(&mut conn, None, event).await.unwrap();
+        }
+        let anomalies = scan(&mut conn, &Workday::default()).await.unwrap();
+        assert_eq!(
+            anomalies,
+            [Anomaly::FutureEvent {
+                id: 3,
+                timestamp: later
+            }]
+        );
+    }
+}
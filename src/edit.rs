@@ -0,0 +1,668 @@
+//! Reversible changes to the history of tasks.
+//!
+//! Each change happens in a single transaction, and returns a [`Revision`] which reverts it.
+//! Changes made through [`undoable`] are also remembered in the database, so that [`undo`] can revert them later.
+
+use chrono::{DateTime, Utc};
+use futures::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection as _, SqliteConnection};
+
+use crate::{
+    backup::BackupEvent,
+    db::{self, EvtType, Id, InsertEvent, Overlap, RetrieveEvent, UpdateEvent},
+};
+
+/// How to revert a change to the events.
+///
+/// It also records the events as the change left them, so that it's only reverted while they're still that way.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Revision {
+    /// The events which the change inserted, as it left them.
+    pub inserted: Vec<BackupEvent>,
+    /// The events which the change deleted, as they were.
+    pub deleted: Vec<BackupEvent>,
+    /// The events which the change updated, as they were before and as it left them.
+    pub updated: Vec<(BackupEvent, BackupEvent)>,
+    /// The deleted STARTs of completed pomodoros, which deleting them forgot.
+    #[serde(default)]
+    pub pomodoros: Vec<Id>,
+}
+
+impl Revision {
+    /// Whether the events are still as the change left them.
+    ///
+    /// Ids are reused once their events are deleted, so an id alone doesn't identify an event.
+    async fn is_current(&self, conn: &mut SqliteConnection) -> Result<bool, Error> {
+        for event in self
+            .inserted
+            .iter()
+            .chain(self.updated.iter().map(|(_, after)| after))
+        {
+            let current = RetrieveEvent::by_id(conn, event.id)
+                .await?
+                .map(BackupEvent::from);
+            if current.as_ref() != Some(event) {
+                return Ok(false);
+            }
+        }
+        for event in &self.deleted {
+            if RetrieveEvent::by_id(conn, event.id).await?.is_some() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Put the events back the way they were before the change.
+    ///
+    /// Fail with [`Error::Changed`], changing nothing, if the events have changed since.
+    pub async fn revert(self, conn: &mut SqliteConnection) -> Result<(), Error> {
+        let mut tx = conn.begin().await.map_err(Error::Transaction)?;
+        if !self.is_current(&mut tx).await? {
+            return Err(Error::Changed);
+        }
+        for event in self.inserted {
+            db::delete_event(&mut tx, event.id).await?;
+        }
+        for event in self.deleted {
+            let BackupEvent {
+                id,
+                evt_type,
+                timestamp,
+                message,
+                provisional,
+            } = event;
+            let event = InsertEvent {
+                evt_type,
+                timestamp,
+                message,
+                provisional,
+            };
+            db::insert_exact(&mut tx, Some(id), event).await?;
+        }
+        for start in self.pomodoros {
+            db::record_pomodoro(&mut tx, start).await?;
+        }
+        for (before, _) in self.updated {
+            UpdateEvent {
+                id: before.id,
+                timestamp: Some(before.timestamp),
+                message: Some(before.message),
+            }
+            .update(&mut tx)
+            .await?;
+        }
+        tx.commit().await.map_err(Error::Transaction)
+    }
+}
+
+/// Make a change, and remember it as `description` so that it can be undone, in a single transaction.
+pub async fn undoable<F>(
+    conn: &mut SqliteConnection,
+    description: &str,
+    change: F,
+) -> Result<(), Error>
+where
+    F: for<'c> FnOnce(&'c mut SqliteConnection) -> LocalBoxFuture<'c, Result<Revision, Error>>,
+{
+    let mut tx = conn.begin().await.map_err(Error::Transaction)?;
+    let revision = change(&mut tx).await?;
+    let revision = serde_json::to_string(&revision).map_err(Error::Serialize)?;
+    db::push_undo(&mut tx, description, &revision).await?;
+    tx.commit().await.map_err(Error::Transaction)
+}
+
+/// Revert the most recent change made through [`undoable`], returning its description.
+///
+/// Return `None` if there is nothing to undo.
+pub async fn undo(conn: &mut SqliteConnection) -> Result<Option<String>, Error> {
+    let mut tx = conn.begin().await.map_err(Error::Transaction)?;
+    let Some((description, revision)) = db::pop_undo(&mut tx).await? else {
+        return Ok(None);
+    };
+    let revision: Revision = serde_json::from_str(&revision).map_err(Error::Deserialize)?;
+    match revision.revert(&mut tx).await {
+        // it can never be undone now, so forget it rather than getting stuck on it
+        Err(Error::Changed) => {
+            tx.commit().await.map_err(Error::Transaction)?;
+            Err(Error::CannotUndo(description))
+        }
+        result => {
+            result?;
+            tx.commit().await.map_err(Error::Transaction)?;
+            Ok(Some(description))
+        }
+    }
+}
+
+/// Delete an event, recording how to restore it, and its pomodoro, if it has one.
+async fn delete(
+    conn: &mut SqliteConnection,
+    event: RetrieveEvent,
+    revision: &mut Revision,
+) -> Result<(), Error> {
+    if db::is_pomodoro(conn, event.id).await? {
+        revision.pomodoros.push(event.id);
+    }
+    db::delete_event(conn, event.id).await?;
+    revision.deleted.push(event.into());
+    Ok(())
+}
+
+/// An event exactly as it is now.
+async fn current(conn: &mut SqliteConnection, id: Id) -> Result<BackupEvent, Error> {
+    RetrieveEvent::by_id(conn, id)
+        .await?
+        .map(Into::into)
+        .ok_or(Error::NotFound(id))
+}
+
+/// The START of the task with this id, and the event which ends it, if any.
+async fn task(
+    conn: &mut SqliteConnection,
+    id: Id,
+) -> Result<(RetrieveEvent, Option<RetrieveEvent>), Error> {
+    let start = RetrieveEvent::by_id(conn, id)
+        .await?
+        .ok_or(Error::NotFound(id))?;
+    if !matches!(start.evt_type, EvtType::Start) {
+        return Err(Error::NotATask(id));
+    }
+    let end = RetrieveEvent::first_after(conn, start.timestamp).await?;
+    Ok((start, end))
+}
+
+/// Delete a task, leaving a gap where it was.
+///
+/// Its STOP is deleted too, and the task before it is stopped when it started, if it wasn't already.
+pub async fn delete_task(conn: &mut SqliteConnection, id: Id) -> Result<Revision, Error> {
+    let (start, end) = task(conn, id).await?;
+    let previous = RetrieveEvent::last_before(conn, start.timestamp).await?;
+    let mut revision = Revision::default();
+
+    let mut tx = conn.begin().await.map_err(Error::Transaction)?;
+    let start_timestamp = start.timestamp;
+    delete(&mut tx, start, &mut revision).await?;
+    if let Some(end) = end.filter(|end| matches!(end.evt_type, EvtType::Stop)) {
+        delete(&mut tx, end, &mut revision).await?;
+    }
+    if previous.is_some_and(|previous| matches!(previous.evt_type, EvtType::Start)) {
+        let stop = InsertEvent {
+            evt_type: EvtType::Stop,
+            timestamp: start_timestamp,
+            message: String::new(),
+            provisional: false,
+        };
+        let stop = stop.insert(&mut tx, Overlap::Allow).await?;
+        revision.inserted.push(current(&mut tx, stop).await?);
+    }
+    tx.commit().await.map_err(Error::Transaction)?;
+
+    Ok(revision)
+}
+
+/// Split a task in two at `at`, so that the second part has `message`.
+///
+/// Without a message, both parts keep the task's message.
+pub async fn split_task(
+    conn: &mut SqliteConnection,
+    id: Id,
+    at: DateTime<Utc>,
+    message: Option<String>,
+) -> Result<Revision, Error> {
+    let (start, end) = task(conn, id).await?;
+    let end = end.map_or_else(Utc::now, |end| end.timestamp);
+    if at <= start.timestamp || at >= end {
+        return Err(Error::OutsideTask(id));
+    }
+
+    let mut tx = conn.begin().await.map_err(Error::Transaction)?;
+    let second = InsertEvent {
+        evt_type: EvtType::Start,
+        timestamp: at,
+        message: message.unwrap_or(start.message),
+        provisional: false,
+    };
+    // splitting the task is the whole point
+    let inserted = second.insert(&mut tx, Overlap::Allow).await?;
+    let inserted = current(&mut tx, inserted).await?;
+    tx.commit().await.map_err(Error::Transaction)?;
+
+    Ok(Revision {
+        inserted: vec![inserted],
+        ..Revision::default()
+    })
+}
+
+/// Merge a task with the task which follows it, removing the events between them.
+///
+/// With a message, the merged task has that message; otherwise it keeps the first task's.
+pub async fn merge_tasks(
+    conn: &mut SqliteConnection,
+    first: Id,
+    second: Id,
+    message: Option<String>,
+) -> Result<Revision, Error> {
+    let (first_start, first_end) = task(conn, first).await?;
+    let (second_start, _) = task(conn, second).await?;
+    let not_adjacent = || Error::NotAdjacent(first, second);
+
+    // the first task ends where the second starts, or it's stopped and the second starts next
+    let first_end = first_end.ok_or_else(not_adjacent)?;
+    let stop = if first_end.id == second_start.id {
+        None
+    } else if matches!(first_end.evt_type, EvtType::Stop) {
+        let next = RetrieveEvent::first_after(conn, first_end.timestamp).await?;
+        if next.is_none_or(|next| next.id != second_start.id) {
+            return Err(not_adjacent());
+        }
+        Some(first_end)
+    } else {
+        return Err(not_adjacent());
+    };
+
+    let mut revision = Revision::default();
+    let mut tx = conn.begin().await.map_err(Error::Transaction)?;
+    if let Some(stop) = stop {
+        delete(&mut tx, stop, &mut revision).await?;
+    }
+    delete(&mut tx, second_start, &mut revision).await?;
+    if let Some(message) = message.filter(|message| *message != first_start.message) {
+        UpdateEvent {
+            id: first,
+            timestamp: None,
+            message: Some(message),
+        }
+        .update(&mut tx)
+        .await?;
+        let after = current(&mut tx, first).await?;
+        revision.updated.push((first_start.into(), after));
+    }
+    tx.commit().await.map_err(Error::Transaction)?;
+
+    Ok(revision)
+}
+
+/// Move an event to another time, without moving it past the events on either side.
+pub async fn move_event(
+    conn: &mut SqliteConnection,
+    id: Id,
+    to: DateTime<Utc>,
+) -> Result<Revision, Error> {
+    let event = RetrieveEvent::by_id(conn, id)
+        .await?
+        .ok_or(Error::NotFound(id))?;
+    keep_order(conn, &event, to).await?;
+
+    UpdateEvent {
+        id,
+        timestamp: Some(to),
+        message: None,
+    }
+    .update(conn)
+    .await?;
+    let after = current(conn, id).await?;
+    Ok(Revision {
+        updated: vec![(event.into(), after)],
+        ..Revision::default()
+    })
+}
+
+/// Refuse to move `event` to `to` if that would move it past the events on either side.
+pub async fn keep_order(
+    conn: &mut SqliteConnection,
+    event: &RetrieveEvent,
+    to: DateTime<Utc>,
+) -> Result<(), Error> {
+    let previous = RetrieveEvent::last_before(conn, event.timestamp).await?;
+    let next = RetrieveEvent::first_after(conn, event.timestamp).await?;
+    if previous.is_some_and(|previous| to <= previous.timestamp)
+        || next.is_some_and(|next| to >= next.timestamp)
+    {
+        return Err(Error::WouldReorder(event.id));
+    }
+    Ok(())
+}
+
+/// Move the event which ends a task to another time.
+pub async fn move_stop(
+    conn: &mut SqliteConnection,
+    id: Id,
+    to: DateTime<Utc>,
+) -> Result<Revision, Error> {
+    let (_, end) = task(conn, id).await?;
+    let end = end.ok_or(Error::StillRunning(id))?;
+    move_event(conn, end.id, to).await
+}
+
+/// Change the message of an event.
+pub async fn rename(
+    conn: &mut SqliteConnection,
+    id: Id,
+    message: String,
+) -> Result<Revision, Error> {
+    let event = RetrieveEvent::by_id(conn, id)
+        .await?
+        .ok_or(Error::NotFound(id))?;
+    UpdateEvent {
+        id,
+        timestamp: None,
+        message: Some(message),
+    }
+    .update(conn)
+    .await?;
+    let after = current(conn, id).await?;
+    Ok(Revision {
+        updated: vec![(event.into(), after)],
+        ..Revision::default()
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("no event with id {0}")]
+    NotFound(Id),
+    #[error("#{0} is not the start of a task")]
+    NotATask(Id),
+    #[error("that time is outside #{0}")]
+    OutsideTask(Id),
+    #[error("#{1} doesn't immediately follow #{0}")]
+    NotAdjacent(Id, Id),
+    #[error("#{0} hasn't stopped yet")]
+    StillRunning(Id),
+    #[error("#{0} can't be moved past the events on either side of it")]
+    WouldReorder(Id),
+    #[error("the events have changed since")]
+    Changed,
+    #[error("\"{0}\" can't be undone, because the events have changed since; it's been forgotten")]
+    CannotUndo(String),
+    #[error("editing history")]
+    Transaction(#[source] sqlx::Error),
+    #[error("recording how to undo a change")]
+    Serialize(#[source] serde_json::Error),
+    #[error("reading how to undo a change")]
+    Deserialize(#[source] serde_json::Error),
+    #[error(transparent)]
+    Db(#[from] db::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backup::{self, Backup},
+        db::MIGRATOR,
+    };
+
+    /// The events' types, times of day, and messages.
+    type Timeline = Vec<(EvtType, String, String)>;
+
+    async fn with_events(events: &[(EvtType, &str, &str)]) -> SqliteConnection {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        MIGRATOR.run(&mut conn).await.unwrap();
+        for (evt_type, time, message) in events {
+            InsertEvent {
+                evt_type: *evt_type,
+                timestamp: at(time),
+                message: (*message).into(),
+                provisional: false,
+            }
+            .insert(&mut conn, Overlap::Allow)
+            .await
+            .unwrap();
+        }
+        conn
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        format!("2024-05-02T{time}:00Z").parse().unwrap()
+    }
+
+    async fn timeline(conn: &mut SqliteConnection) -> Timeline {
+        RetrieveEvent::all(conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| {
+                let time = event.timestamp.format("%H:%M").to_string();
+                (event.evt_type, time, event.message)
+            })
+            .collect()
+    }
+
+    fn expected(events: &[(EvtType, &str, &str)]) -> Timeline {
+        events
+            .iter()
+            .map(|(evt_type, time, message)| (*evt_type, (*time).to_owned(), (*message).to_owned()))
+            .collect()
+    }
+
+    use EvtType::{Start, Stop};
+
+    #[tokio::test]
+    async fn delete_between_tasks() {
+        let events = [
+            (Start, "09:00", "a"),
+            (Start, "10:00", "b"),
+            (Stop, "11:00", ""),
+            (Start, "12:00", "c"),
+        ];
+        let mut conn = with_events(&events).await;
+        let revision = delete_task(&mut conn, 2).await.unwrap();
+        assert_eq!(
+            timeline(&mut conn).await,
+            expected(&[
+                (Start, "09:00", "a"),
+                (Stop, "10:00", ""),
+                (Start, "12:00", "c")
+            ])
+        );
+
+        revision.revert(&mut conn).await.unwrap();
+        assert_eq!(timeline(&mut conn).await, expected(&events));
+    }
+
+    #[tokio::test]
+    async fn split_and_revert() {
+        let events = [(Start, "09:00", "a"), (Stop, "11:00", "")];
+        let mut conn = with_events(&events).await;
+        let revision = split_task(&mut conn, 1, at("10:00"), Some("b".into()))
+            .await
+            .unwrap();
+        assert_eq!(
+            timeline(&mut conn).await,
+            expected(&[
+                (Start, "09:00", "a"),
+                (Start, "10:00", "b"),
+                (Stop, "11:00", "")
+            ])
+        );
+        assert!(matches!(
+            split_task(&mut conn, 1, at("10:30"), None).await,
+            Err(Error::OutsideTask(1))
+        ));
+
+        revision.revert(&mut conn).await.unwrap();
+        assert_eq!(timeline(&mut conn).await, expected(&events));
+    }
+
+    #[tokio::test]
+    async fn merge_across_a_stop() {
+        let events = [
+            (Start, "09:00", "a"),
+            (Stop, "10:00", ""),
+            (Start, "10:15", "b"),
+            (Stop, "11:00", ""),
+        ];
+        let mut conn = with_events(&events).await;
+        let revision = merge_tasks(&mut conn, 1, 3, Some("a and b".into()))
+            .await
+            .unwrap();
+        assert_eq!(
+            timeline(&mut conn).await,
+            expected(&[(Start, "09:00", "a and b"), (Stop, "11:00", "")])
+        );
+
+        revision.revert(&mut conn).await.unwrap();
+        assert_eq!(timeline(&mut conn).await, expected(&events));
+    }
+
+    #[tokio::test]
+    async fn revert_merge_keeps_pomodoros() {
+        let events = [
+            (Start, "09:00", "a"),
+            (Start, "09:30", "b"),
+            (Stop, "09:55", ""),
+        ];
+        let mut conn = with_events(&events).await;
+        db::record_pomodoro(&mut conn, 2).await.unwrap();
+
+        let revision = merge_tasks(&mut conn, 1, 2, None).await.unwrap();
+        assert_eq!(revision.pomodoros, [2]);
+        assert!(db::all_pomodoros(&mut conn).await.unwrap().is_empty());
+
+        revision.revert(&mut conn).await.unwrap();
+        assert_eq!(timeline(&mut conn).await, expected(&events));
+        assert_eq!(db::all_pomodoros(&mut conn).await.unwrap(), [2]);
+    }
+
+    #[tokio::test]
+    async fn merge_needs_adjacent_tasks() {
+        let events = [
+            (Start, "09:00", "a"),
+            (Start, "10:00", "b"),
+            (Start, "11:00", "c"),
+        ];
+        let mut conn = with_events(&events).await;
+        assert!(matches!(
+            merge_tasks(&mut conn, 1, 3, None).await,
+            Err(Error::NotAdjacent(1, 3))
+        ));
+        merge_tasks(&mut conn, 2, 3, None).await.unwrap();
+        assert_eq!(
+            timeline(&mut conn).await,
+            expected(&[(Start, "09:00", "a"), (Start, "10:00", "b")])
+        );
+    }
+
+    #[tokio::test]
+    async fn undo_most_recent_first() {
+        let events = [(Start, "09:00", "a"), (Stop, "11:00", "")];
+        let mut conn = with_events(&events).await;
+        undoable(&mut conn, "split", |conn| {
+            Box::pin(split_task(conn, 1, at("10:00"), Some("b".into())))
+        })
+        .await
+        .unwrap();
+        undoable(&mut conn, "merge", |conn| {
+            Box::pin(merge_tasks(conn, 1, 3, Some("a and b".into())))
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            timeline(&mut conn).await,
+            expected(&[(Start, "09:00", "a and b"), (Stop, "11:00", "")])
+        );
+
+        assert_eq!(undo(&mut conn).await.unwrap().as_deref(), Some("merge"));
+        assert_eq!(
+            timeline(&mut conn).await,
+            expected(&[
+                (Start, "09:00", "a"),
+                (Start, "10:00", "b"),
+                (Stop, "11:00", "")
+            ])
+        );
+        assert_eq!(undo(&mut conn).await.unwrap().as_deref(), Some("split"));
+        assert_eq!(timeline(&mut conn).await, expected(&events));
+        assert_eq!(undo(&mut conn).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn failed_changes_are_not_remembered() {
+        let events = [(Start, "09:00", "a"), (Stop, "11:00", "")];
+        let mut conn = with_events(&events).await;
+        let split = undoable(&mut conn, "split", |conn| {
+            Box::pin(split_task(conn, 1, at("12:00"), None))
+        })
+        .await;
+        assert!(matches!(split, Err(Error::OutsideTask(1))));
+        assert_eq!(undo(&mut conn).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn undo_refuses_reused_ids() {
+        let events = [(Start, "09:00", "a"), (Stop, "11:00", "")];
+        let mut conn = with_events(&events).await;
+        undoable(&mut conn, "split", |conn| {
+            Box::pin(split_task(conn, 1, at("10:00"), Some("b".into())))
+        })
+        .await
+        .unwrap();
+        // the split's start is deleted, and its id reused by an unrelated event
+        db::delete_event(&mut conn, 3).await.unwrap();
+        let foo = InsertEvent {
+            evt_type: Start,
+            timestamp: at("12:00"),
+            message: "foo".into(),
+            provisional: false,
+        }
+        .insert(&mut conn, Overlap::Allow)
+        .await
+        .unwrap();
+        assert_eq!(foo, 3);
+
+        assert!(matches!(
+            undo(&mut conn).await,
+            Err(Error::CannotUndo(description)) if description == "split"
+        ));
+        assert_eq!(
+            timeline(&mut conn).await,
+            expected(&[
+                (Start, "09:00", "a"),
+                (Stop, "11:00", ""),
+                (Start, "12:00", "foo")
+            ])
+        );
+        // the stale change is forgotten
+        assert_eq!(undo(&mut conn).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn replacing_restore_clears_undo() {
+        let events = [(Start, "09:00", "a"), (Stop, "11:00", "")];
+        let mut conn = with_events(&events).await;
+        let backup = Backup::dump(&mut conn).await.unwrap();
+        undoable(&mut conn, "split", |conn| {
+            Box::pin(split_task(conn, 1, at("10:00"), Some("b".into())))
+        })
+        .await
+        .unwrap();
+
+        backup
+            .restore(&mut conn, backup::Mode::Replace)
+            .await
+            .unwrap();
+        assert_eq!(undo(&mut conn).await.unwrap(), None);
+        assert_eq!(timeline(&mut conn).await, expected(&events));
+    }
+
+    #[tokio::test]
+    async fn moves_stay_in_order() {
+        let events = [
+            (Start, "09:00", "a"),
+            (Stop, "10:00", ""),
+            (Start, "11:00", "b"),
+        ];
+        let mut conn = with_events(&events).await;
+        assert!(matches!(
+            move_event(&mut conn, 2, at("11:30")).await,
+            Err(Error::WouldReorder(2))
+        ));
+        let revision = move_event(&mut conn, 2, at("10:30")).await.unwrap();
+        assert_eq!(timeline(&mut conn).await[1].1, "10:30");
+        revision.revert(&mut conn).await.unwrap();
+        assert_eq!(timeline(&mut conn).await, expected(&events));
+    }
+}
@@ -0,0 +1,189 @@
+//! Integration with git repositories, by way of the `git` command.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use chrono::{DateTime, Utc};
+
+/// Identifies hooks which `worklog` installed, so that it never touches anyone else's.
+const HOOK_MARKER: &str = "# installed by worklog";
+
+/// Run `git` in the repository containing `repo`, returning its trimmed stdout.
+fn git(repo: &Path, args: &[&str]) -> Result<String, Error> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .map_err(Error::Spawn)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Git(stderr.trim().to_owned()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// The path of the `post-checkout` hook of the repository containing the current directory.
+///
+/// This respects `core.hooksPath` and linked worktrees.
+fn post_checkout_hook() -> Result<PathBuf, Error> {
+    let hooks = PathBuf::from(git(Path::new("."), &["rev-parse", "--git-path", "hooks"])?);
+    Ok(hooks.join("post-checkout"))
+}
+
+/// Whether the hook at `path` was installed by `worklog`.
+fn is_ours(path: &Path) -> Result<bool, Error> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(contents.lines().any(|line| line.starts_with(HOOK_MARKER))),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(Error::Hook(path.to_owned(), err)),
+    }
+}
+
+/// Install a `post-checkout` hook which starts a task for each checked-out branch, returning its path.
+///
+/// This replaces a hook previously installed by `worklog`, but refuses to replace anyone else's.
+pub fn install_hooks() -> Result<PathBuf, Error> {
+    let path = post_checkout_hook()?;
+    if path.exists() && !is_ours(&path)? {
+        return Err(Error::ForeignHook(path));
+    }
+
+    let exe = std::env::current_exe().map_err(Error::CurrentExe)?;
+    let exe = exe.display().to_string().replace('\'', r"'\''");
+    let script = format!(
+        "#!/bin/sh\n\
+         {HOOK_MARKER}; remove it with `worklog git uninstall-hooks`\n\
+         '{exe}' git post-checkout \"$@\" || true\n"
+    );
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| Error::Hook(path.clone(), err))?;
+    }
+    std::fs::write(&path, script).map_err(|err| Error::Hook(path.clone(), err))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+            .map_err(|err| Error::Hook(path.clone(), err))?;
+    }
+
+    Ok(path)
+}
+
+/// Remove the `post-checkout` hook installed by `worklog`, returning its path if there was one.
+pub fn uninstall_hooks() -> Result<Option<PathBuf>, Error> {
+    let path = post_checkout_hook()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    if !is_ours(&path)? {
+        return Err(Error::ForeignHook(path));
+    }
+    std::fs::remove_file(&path).map_err(|err| Error::Hook(path.clone(), err))?;
+    Ok(Some(path))
+}
+
+/// The name of the currently checked-out branch, or `None` if `HEAD` is detached.
+pub fn current_branch() -> Result<Option<String>, Error> {
+    match git(Path::new("."), &["symbolic-ref", "--short", "-q", "HEAD"]) {
+        Ok(branch) if !branch.is_empty() => Ok(Some(branch)),
+        // `symbolic-ref -q` fails quietly for a detached HEAD
+        Ok(_) | Err(Error::Git(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// A commit from a local repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commit {
+    /// The abbreviated commit hash.
+    pub hash: String,
+    pub author_time: DateTime<Utc>,
+    /// The first line of the commit message.
+    pub summary: String,
+}
+
+/// Parse a line of `git log --format=%h%x1f%at%x1f%s`.
+fn parse_commit(line: &str) -> Option<Commit> {
+    let mut fields = line.splitn(3, '\x1f');
+    let hash = fields.next()?.to_owned();
+    let author_time = DateTime::from_timestamp(fields.next()?.parse().ok()?, 0)?;
+    let summary = fields.next()?.to_owned();
+    Some(Commit {
+        hash,
+        author_time,
+        summary,
+    })
+}
+
+/// The commits on any branch of the repository at `repo` which were authored between `start` (inclusive)
+/// and `end` (exclusive), oldest first.
+///
+/// Only the configured user's own commits are included, if `user.email` is set.
+/// This only reads local history; nothing is fetched.
+pub fn commits_between(
+    repo: &Path,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<Commit>, Error> {
+    // git filters on the commit time, which is never before the author time
+    let since = format!("--since=@{}", start.timestamp());
+    let mut args = vec![
+        "log",
+        "--all",
+        "--no-merges",
+        "--format=%h%x1f%at%x1f%s",
+        &since,
+    ];
+    let author = git(repo, &["config", "user.email"])
+        .ok()
+        .map(|email| format!("--author=<{email}>"));
+    if let Some(author) = &author {
+        args.push(author);
+    }
+
+    let mut commits: Vec<_> = git(repo, &args)?
+        .lines()
+        .filter_map(parse_commit)
+        .filter(|commit| start <= commit.author_time && commit.author_time < end)
+        .collect();
+    commits.sort_by_key(|commit| commit.author_time);
+    Ok(commits)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("running git")]
+    Spawn(#[source] std::io::Error),
+    #[error("git failed: {0}")]
+    Git(String),
+    #[error("finding the worklog executable")]
+    CurrentExe(#[source] std::io::Error),
+    #[error("writing hook {0}")]
+    Hook(PathBuf, #[source] std::io::Error),
+    #[error("{0} was not installed by worklog; leaving it alone")]
+    ForeignHook(PathBuf),
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone as _;
+
+    use super::*;
+
+    #[test]
+    fn parse_log_line() {
+        assert_eq!(
+            parse_commit("4b825dc\x1f1714640400\x1ffix: handle \x1f in summaries"),
+            Some(Commit {
+                hash: "4b825dc".into(),
+                author_time: Utc.with_ymd_and_hms(2024, 5, 2, 9, 0, 0).unwrap(),
+                summary: "fix: handle \x1f in summaries".into(),
+            })
+        );
+        assert_eq!(parse_commit("not a commit"), None);
+    }
+}
@@ -0,0 +1,134 @@
+//! Noticing when the user has stopped using the computer.
+
+use std::process::Command;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::config;
+
+/// How often to check whether the user is idle.
+pub const POLL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Where to find out how long the user has been idle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// A shell command which prints the idle time in milliseconds.
+    Command(String),
+    /// The idle hint which logind keeps for the current session.
+    Logind,
+}
+
+impl From<&config::Idle> for Source {
+    fn from(config: &config::Idle) -> Self {
+        match &config.command {
+            Some(command) => Self::Command(command.clone()),
+            None => Self::Logind,
+        }
+    }
+}
+
+impl Source {
+    /// When the user went idle, if they've been idle for at least `threshold`.
+    pub fn idle_since(
+        &self,
+        threshold: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<Option<DateTime<Utc>>, Error> {
+        let since = match self {
+            Self::Command(command) => {
+                let output = run(Command::new("sh").arg("-c").arg(command))?;
+                parse_idle_millis(&output, now)?
+            }
+            Self::Logind => {
+                let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".into());
+                let output = run(Command::new("loginctl").args([
+                    "show-session",
+                    &session,
+                    "--property=IdleHint",
+                    "--property=IdleSinceHint",
+                ]))?;
+                parse_logind(&output)?
+            }
+        };
+        Ok(since.filter(|since| now - *since >= threshold))
+    }
+}
+
+fn run(command: &mut Command) -> Result<String, Error> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .map_err(|err| Error::Run(program.clone(), err))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(Error::Failed(program, stderr));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Interpret the output of an idle command, like `xprintidle`.
+fn parse_idle_millis(output: &str, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, Error> {
+    let millis: i64 = output
+        .trim()
+        .parse()
+        .map_err(|_| Error::Parse(output.trim().to_owned()))?;
+    Ok((millis > 0).then(|| now - Duration::milliseconds(millis)))
+}
+
+/// Interpret the properties printed by `loginctl show-session`.
+fn parse_logind(output: &str) -> Result<Option<DateTime<Utc>>, Error> {
+    let property = |name: &str| {
+        output.lines().find_map(|line| {
+            line.strip_prefix(name)
+                .and_then(|line| line.strip_prefix('='))
+        })
+    };
+    let parse_error = || Error::Parse(output.trim().to_owned());
+
+    if property("IdleHint").ok_or_else(parse_error)? != "yes" {
+        return Ok(None);
+    }
+    let micros: i64 = property("IdleSinceHint")
+        .and_then(|since| since.parse().ok())
+        .ok_or_else(parse_error)?;
+    Ok(DateTime::from_timestamp_micros(micros))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("running {0}")]
+    Run(String, #[source] std::io::Error),
+    #[error("{0} failed: {1}")]
+    Failed(String, String),
+    #[error("unexpected idle time: \"{0}\"")]
+    Parse(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_millis() {
+        let now = "2024-05-02T10:00:00Z".parse().unwrap();
+        assert_eq!(
+            parse_idle_millis("90000\n", now).unwrap(),
+            Some("2024-05-02T09:58:30Z".parse().unwrap())
+        );
+        assert_eq!(parse_idle_millis("0", now).unwrap(), None);
+        assert!(parse_idle_millis("idle", now).is_err());
+    }
+
+    #[test]
+    fn logind() {
+        assert_eq!(
+            parse_logind("IdleHint=yes\nIdleSinceHint=1714643910000000\n").unwrap(),
+            Some("2024-05-02T09:58:30Z".parse().unwrap())
+        );
+        assert_eq!(
+            parse_logind("IdleHint=no\nIdleSinceHint=0\n").unwrap(),
+            None
+        );
+        assert!(parse_logind("").is_err());
+    }
+}
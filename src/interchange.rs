@@ -0,0 +1,321 @@
+//! Moving data between `worklog` and other time trackers.
+//!
+//! Each supported tracker has a module here, with a type implementing [`Importer`], [`Exporter`], or both.
+
+pub mod ics;
+pub mod org;
+pub mod timeclock;
+pub mod timewarrior;
+pub mod toggl;
+pub mod watson;
+
+use std::{
+    fmt,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, SubsecRound as _, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+
+use crate::{
+    config::Config,
+    db::{self, EvtType, InsertEvent, Overlap},
+    task::Task,
+};
+
+/// A span of work, as other time trackers see it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Interval {
+    pub start: DateTime<Utc>,
+    /// `None` if the interval is still in progress.
+    pub stop: Option<DateTime<Utc>>,
+    pub message: String,
+}
+
+/// Reads intervals from another time tracker's data.
+pub trait Importer {
+    /// Read all intervals from the data at `path`.
+    fn read(&self, path: &Path) -> Result<Vec<Interval>, Error>;
+}
+
+/// Writes tasks in another time tracker's format.
+pub trait Exporter {
+    /// Write all `tasks` to `out`.
+    ///
+    /// Tasks which are still in progress may be skipped, if the format has no way to represent them.
+    fn write(&self, tasks: &[Task], config: &Config, out: &mut dyn Write) -> Result<(), Error>;
+}
+
+/// Formats which `worklog` can exchange data with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    Timewarrior,
+    Watson,
+    Toggl,
+    /// iCalendar, for overlaying tasks on a calendar.
+    Ics,
+    /// ledger and hledger's timeclock format, for billing.
+    Timeclock,
+    Org,
+}
+
+impl Format {
+    /// The importer for this format, if it can be imported.
+    pub fn importer(self) -> Option<&'static dyn Importer> {
+        match self {
+            Self::Timewarrior => Some(&timewarrior::Timewarrior),
+            Self::Watson => Some(&watson::Watson),
+            Self::Toggl => Some(&toggl::Toggl),
+            Self::Org => Some(&org::Org),
+            Self::Ics | Self::Timeclock => None,
+        }
+    }
+
+    /// The exporter for this format, if it can be exported.
+    pub fn exporter(self) -> Option<&'static dyn Exporter> {
+        match self {
+            Self::Timewarrior => None,
+            Self::Watson => Some(&watson::Watson),
+            Self::Toggl => Some(&toggl::Toggl),
+            Self::Ics => Some(&ics::Ics),
+            Self::Timeclock => Some(&timeclock::Timeclock),
+            Self::Org => Some(&org::Org),
+        }
+    }
+
+    /// Read all intervals from the data at `path`.
+    pub fn read(self, path: &Path) -> Result<Vec<Interval>, Error> {
+        self.importer().ok_or(Error::CannotImport(self))?.read(path)
+    }
+
+    /// Write all `tasks` to `out` in this format.
+    pub fn write(self, tasks: &[Task], config: &Config, out: &mut dyn Write) -> Result<(), Error> {
+        self.exporter()
+            .ok_or(Error::CannotExport(self))?
+            .write(tasks, config, out)
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Timewarrior => "timewarrior",
+            Self::Watson => "watson",
+            Self::Toggl => "toggl",
+            Self::Ics => "ics",
+            Self::Timeclock => "timeclock",
+            Self::Org => "org",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Compose a message from some descriptive text and a list of tags.
+///
+/// Tags are written with a leading `+`, as Watson does, so that [`split_tags`] can recover them.
+fn join_tags<'a>(
+    text: impl IntoIterator<Item = &'a str>,
+    tags: impl IntoIterator<Item = &'a str>,
+) -> String {
+    text.into_iter()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(ToOwned::to_owned)
+        .chain(
+            tags.into_iter()
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(|tag| format!("+{tag}")),
+        )
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Split a message into its descriptive text and its `+tags`.
+fn split_tags(message: &str) -> (String, Vec<String>) {
+    let (tags, text): (Vec<_>, Vec<_>) = message
+        .split_whitespace()
+        .partition(|word| word.len() > 1 && word.starts_with('+'));
+    let tags = tags.into_iter().map(|tag| tag[1..].to_owned()).collect();
+    (text.join(" "), tags)
+}
+
+/// What happened, or would happen, when importing some intervals.
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub imported: usize,
+    /// Intervals whose START is already present.
+    pub duplicates: usize,
+    /// Intervals which would split or truncate existing tasks.
+    pub conflicts: usize,
+}
+
+/// Import intervals as START/STOP pairs, in order of their start.
+///
+/// Intervals which were already imported are skipped, as are those which would reshape existing tasks
+/// unless `overlap` allows it. A dry run inserts nothing, and does not detect conflicts between the
+/// imported intervals themselves.
+pub async fn import(
+    conn: &mut SqliteConnection,
+    mut intervals: Vec<Interval>,
+    overlap: Overlap,
+    dry_run: bool,
+) -> Result<ImportSummary, Error> {
+    intervals.sort_by_key(|interval| interval.start);
+
+    let mut summary = ImportSummary::default();
+    for Interval {
+        start,
+        stop,
+        message,
+    } in intervals
+    {
+        // other trackers only keep whole seconds, so a START within the same second is the same one
+        let second = start.trunc_subsecs(0);
+        if db::event_exists_between(
+            conn,
+            EvtType::Start,
+            second,
+            second + Duration::seconds(1),
+            &message,
+        )
+        .await?
+        {
+            summary.duplicates += 1;
+            continue;
+        }
+
+        if dry_run {
+            let reshaped = match overlap {
+                Overlap::Refuse => db::reshaped_by(conn, start, stop.unwrap_or(start)).await?,
+                Overlap::Allow => Vec::new(),
+            };
+            if reshaped.is_empty() {
+                summary.imported += 1;
+            } else {
+                summary.conflicts += 1;
+            }
+            continue;
+        }
+
+        let start = InsertEvent {
+            evt_type: EvtType::Start,
+            timestamp: start,
+            message,
+            provisional: false,
+        };
+        let inserted = match stop {
+            Some(stop) => {
+                let stop = InsertEvent {
+                    evt_type: EvtType::Stop,
+                    timestamp: stop,
+                    message: String::new(),
+                    provisional: false,
+                };
+                db::insert_range(conn, start, stop, overlap)
+                    .await
+                    .map(|_| ())
+            }
+            None => start.insert(conn, overlap).await.map(|_| ()),
+        };
+        match inserted {
+            Ok(()) => summary.imported += 1,
+            Err(db::Error::ReshapesHistory(_)) => summary.conflicts += 1,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(summary)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0} data cannot be imported")]
+    CannotImport(Format),
+    #[error("{0} data cannot be exported")]
+    CannotExport(Format),
+    #[error("reading {0}")]
+    Read(PathBuf, #[source] std::io::Error),
+    #[error("parsing JSON from {0}")]
+    ParseJson(PathBuf, #[source] serde_json::Error),
+    #[error("parsing CSV from {0}")]
+    ParseCsv(PathBuf, #[source] csv::Error),
+    #[error("invalid timestamp in {0}")]
+    InvalidTimestamp(PathBuf),
+    #[error("writing exported data")]
+    Write(#[from] std::io::Error),
+    #[error("writing exported JSON")]
+    WriteJson(#[source] serde_json::Error),
+    #[error("writing exported CSV")]
+    WriteCsv(#[source] csv::Error),
+    #[error("parsing {path} line {line}")]
+    Parse {
+        path: PathBuf,
+        line: usize,
+        #[source]
+        source: peg::error::ParseError<peg::str::LineCol>,
+    },
+    #[error("importing into the database")]
+    Db(#[from] db::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection as _;
+
+    use super::*;
+    use crate::db::{RetrieveEvent, MIGRATOR};
+
+    /// A database holding a single task from `start` to `stop`, and that task.
+    pub(super) async fn logged(
+        start: DateTime<Utc>,
+        stop: DateTime<Utc>,
+        message: &str,
+    ) -> (SqliteConnection, Task) {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        MIGRATOR.run(&mut conn).await.unwrap();
+        let start = InsertEvent {
+            evt_type: EvtType::Start,
+            timestamp: start,
+            message: message.into(),
+            provisional: false,
+        };
+        let stop = InsertEvent {
+            evt_type: EvtType::Stop,
+            timestamp: stop,
+            message: String::new(),
+            provisional: false,
+        };
+        db::insert_range(&mut conn, start, stop, Overlap::Refuse)
+            .await
+            .unwrap();
+        let mut tasks = Task::from_events(RetrieveEvent::all(&mut conn).await.unwrap());
+        (conn, tasks.remove(0))
+    }
+
+    /// Importing what was exported from `conn` finds that it's all there already.
+    pub(super) async fn reimport(conn: &mut SqliteConnection, intervals: Vec<Interval>) {
+        let summary = import(conn, intervals, Overlap::Refuse, false)
+            .await
+            .unwrap();
+        assert_eq!(summary.imported, 0);
+        assert_eq!(summary.duplicates, 1);
+        assert_eq!(summary.conflicts, 0);
+    }
+
+    #[test]
+    fn tags_round_trip() {
+        let message = join_tags(["worklog", " fix the parser "], ["review", "", "#1234"]);
+        assert_eq!(message, "worklog fix the parser +review +#1234");
+        assert_eq!(
+            split_tags(&message),
+            (
+                "worklog fix the parser".to_owned(),
+                vec!["review".to_owned(), "#1234".to_owned()]
+            )
+        );
+    }
+}
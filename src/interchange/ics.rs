@@ -0,0 +1,152 @@
+//! iCalendar ([RFC 5545](https://datatracker.ietf.org/doc/html/rfc5545)), for overlaying tasks on a calendar.
+//!
+//! Each task becomes a VEVENT whose UID is derived from the id of its START event.
+//! Importing the same tasks again updates the existing calendar events instead of duplicating them.
+
+use std::io::Write;
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use super::{Error, Exporter};
+use crate::{config::Config, task::Task};
+
+/// Exports tasks as an iCalendar file.
+pub struct Ics;
+
+/// Content lines should be folded to at most this many octets, excluding the line break.
+const MAX_LINE_LEN: usize = 75;
+
+fn timestamp<Tz: TimeZone>(dt: &DateTime<Tz>) -> String {
+    dt.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape a TEXT value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Write a content line, folding it if it's too long.
+fn write_line(out: &mut dyn Write, line: &str) -> Result<(), Error> {
+    let mut remaining = line;
+    // continuation lines begin with a space, which counts against their length
+    let mut limit = MAX_LINE_LEN;
+    while remaining.len() > limit {
+        let mut split = limit;
+        while !remaining.is_char_boundary(split) {
+            split -= 1;
+        }
+        let (head, tail) = remaining.split_at(split);
+        write!(out, "{head}\r\n ")?;
+        remaining = tail;
+        limit = MAX_LINE_LEN - 1;
+    }
+    write!(out, "{remaining}\r\n")?;
+    Ok(())
+}
+
+impl Exporter for Ics {
+    fn write(&self, tasks: &[Task], config: &Config, out: &mut dyn Write) -> Result<(), Error> {
+        let now = timestamp(&Utc::now());
+        write_line(out, "BEGIN:VCALENDAR")?;
+        write_line(out, "VERSION:2.0")?;
+        write_line(
+            out,
+            concat!(
+                "PRODID:-//worklog//worklog ",
+                env!("CARGO_PKG_VERSION"),
+                "//EN"
+            ),
+        )?;
+        write_line(out, "CALSCALE:GREGORIAN")?;
+
+        for task in tasks {
+            let Some(stop) = task.stop else {
+                continue;
+            };
+            let links = config.links.links(&task.message);
+
+            write_line(out, "BEGIN:VEVENT")?;
+            write_line(out, &format!("UID:worklog-event-{}", task.id))?;
+            write_line(out, &format!("DTSTAMP:{now}"))?;
+            write_line(out, &format!("DTSTART:{}", timestamp(&task.start)))?;
+            write_line(out, &format!("DTEND:{}", timestamp(&stop)))?;
+            write_line(out, &format!("SUMMARY:{}", escape(&task.message)))?;
+            if let Some(url) = links.first() {
+                write_line(out, &format!("URL:{url}"))?;
+                write_line(out, &format!("DESCRIPTION:{}", escape(&links.join("\n"))))?;
+            }
+            write_line(out, "END:VEVENT")?;
+        }
+
+        write_line(out, "END:VCALENDAR")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Local;
+
+    use super::*;
+    use crate::config::Links;
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(escape("a, b; c\\d\ne"), r"a\, b\; c\\d\ne");
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let mut out = Vec::new();
+        let line = format!("SUMMARY:{}", "é".repeat(60));
+        write_line(&mut out, &line).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        for folded in out.split("\r\n") {
+            assert!(folded.len() <= MAX_LINE_LEN);
+        }
+        assert_eq!(out.replace("\r\n ", ""), format!("{line}\r\n"));
+    }
+
+    #[test]
+    fn event_with_issue_link() {
+        let start = Utc.with_ymd_and_hms(2024, 5, 2, 9, 0, 0).unwrap();
+        let task = Task::closed(
+            12,
+            start.with_timezone(&Local),
+            (start + chrono::Duration::minutes(90)).with_timezone(&Local),
+            "#1234: fix parser",
+        );
+        let config = Config {
+            links: Links {
+                default_org: Some("me".into()),
+                default_repo: Some("worklog".into()),
+            },
+            ..Config::default()
+        };
+
+        let mut out = Vec::new();
+        Ics.write(&[task], &config, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        for expect in [
+            "UID:worklog-event-12",
+            "DTSTART:20240502T090000Z",
+            "DTEND:20240502T103000Z",
+            "SUMMARY:#1234: fix parser",
+            "URL:https://github.com/me/worklog/issues/1234",
+        ] {
+            assert!(lines.contains(&expect), "missing {expect:?} in {out}");
+        }
+    }
+}
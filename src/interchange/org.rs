@@ -0,0 +1,203 @@
+//! Org-mode keeps clocked time as CLOCK lines under the heading being worked on:
+//!
+//! ```text
+//! * 2024-05-02 Thu
+//! ** fix the parser
+//!    CLOCK: [2024-05-02 Thu 09:00]--[2024-05-02 Thu 10:30] =>  1:30
+//! ```
+//!
+//! Exports have a heading per day, with a subheading per task message. Imports take each CLOCK line's message from
+//! the nearest heading above it, with any org tags as `+tags`. Timestamps are in local time.
+
+use std::{io::Write, path::Path};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone as _, Utc};
+
+use super::{join_tags, Error, Exporter, Importer, Interval};
+use crate::{config::Config, task::Task};
+
+/// Imports and exports org-mode CLOCK lines.
+pub struct Org;
+
+const TIMESTAMP: &str = "[%Y-%m-%d %a %H:%M]";
+
+/// The tasks on a single day, grouped by message.
+type Day<'a> = (NaiveDate, Vec<(&'a str, Vec<&'a Task>)>);
+
+peg::parser! {
+    grammar org_parser() for str {
+        rule ws() = [' ' | '\t']
+
+        // the day name is ignored, as it may be in any language
+        rule timestamp() -> NaiveDateTime
+            = "[" date:$(['0'..='9']*<4> "-" ['0'..='9']*<2> "-" ['0'..='9']*<2>) ws()+
+                (!(ws() / "]") [_])+ ws()+ time:$(['0'..='9']*<1,2> ":" ['0'..='9']*<2>) "]" {?
+                NaiveDateTime::parse_from_str(&format!("{date} {time}"), "%Y-%m-%d %H:%M")
+                    .map_err(|_| "timestamp")
+            }
+
+        // a clock which is still running has no stop
+        pub rule clock() -> (NaiveDateTime, Option<NaiveDateTime>)
+            = ws()* "CLOCK:" ws()+ start:timestamp() stop:("--" stop:timestamp() { stop })? [_]* {
+                (start, stop)
+            }
+    }
+}
+
+/// The message for a heading line, or `None` if this isn't a heading.
+///
+/// TODO keywords are dropped, and trailing org tags like `:review:urgent:` become `+tags`.
+fn heading(line: &str) -> Option<String> {
+    let title = line.strip_prefix('*')?.trim_start_matches('*');
+    if !title.starts_with([' ', '\t']) {
+        return None;
+    }
+
+    let mut words: Vec<_> = title.split_whitespace().collect();
+    if words
+        .first()
+        .is_some_and(|word| matches!(*word, "TODO" | "DONE"))
+    {
+        words.remove(0);
+    }
+    let tags = match words.last() {
+        Some(last) if last.len() > 1 && last.starts_with(':') && last.ends_with(':') => {
+            let tags = words.pop().expect("last word exists");
+            tags.split(':').collect()
+        }
+        _ => Vec::new(),
+    };
+
+    Some(join_tags(words, tags))
+}
+
+fn local(path: &Path, naive: NaiveDateTime) -> Result<DateTime<Utc>, Error> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(Into::into)
+        .ok_or_else(|| Error::InvalidTimestamp(path.to_owned()))
+}
+
+fn parse(path: &Path, contents: &str) -> Result<Vec<Interval>, Error> {
+    let mut message = String::new();
+    let mut intervals = Vec::new();
+    for line in contents.lines() {
+        if let Some(title) = heading(line) {
+            message = title;
+        } else if let Ok((start, stop)) = org_parser::clock(line) {
+            intervals.push(Interval {
+                start: local(path, start)?,
+                stop: stop.map(|stop| local(path, stop)).transpose()?,
+                message: message.clone(),
+            });
+        }
+    }
+    Ok(intervals)
+}
+
+impl Importer for Org {
+    fn read(&self, path: &Path) -> Result<Vec<Interval>, Error> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|err| Error::Read(path.to_owned(), err))?;
+        parse(path, &contents)
+    }
+}
+
+impl Exporter for Org {
+    fn write(&self, tasks: &[Task], _config: &Config, out: &mut dyn Write) -> Result<(), Error> {
+        // group the tasks by day, then by message, keeping the order in which each first appears
+        let mut days: Vec<Day> = Vec::new();
+        for task in tasks {
+            let date = task.start.date_naive();
+            if days.last().is_none_or(|(day, _)| *day != date) {
+                days.push((date, Vec::new()));
+            }
+            let (_, headings) = days.last_mut().expect("just ensured a day exists");
+            match headings
+                .iter_mut()
+                .find(|(message, _)| *message == task.message)
+            {
+                Some((_, clocked)) => clocked.push(task),
+                None => headings.push((&task.message, vec![task])),
+            }
+        }
+
+        for (date, headings) in days {
+            writeln!(out, "* {}", date.format("%Y-%m-%d %a"))?;
+            for (message, clocked) in headings {
+                writeln!(out, "** {message}")?;
+                for task in clocked {
+                    let start = task.start.format(TIMESTAMP);
+                    match task.stop {
+                        Some(stop) => {
+                            let minutes = (stop - task.start).num_minutes();
+                            let hours = minutes / 60;
+                            let minutes = minutes % 60;
+                            let stop = stop.format(TIMESTAMP);
+                            writeln!(out, "   CLOCK: {start}--{stop} => {hours:2}:{minutes:02}")?;
+                        }
+                        None => writeln!(out, "   CLOCK: {start}")?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heading_with_keyword_and_tags() {
+        assert_eq!(
+            heading("** TODO fix the parser :review:urgent:").as_deref(),
+            Some("fix the parser +review +urgent")
+        );
+        assert_eq!(heading("*bold* text"), None);
+    }
+
+    #[test]
+    fn closed_clock() {
+        let (start, stop) =
+            org_parser::clock("   CLOCK: [2024-05-02 Thu 09:00]--[2024-05-02 Thu 10:30] =>  1:30")
+                .unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+        assert_eq!(start, date.and_hms_opt(9, 0, 0).unwrap());
+        assert_eq!(stop, date.and_hms_opt(10, 30, 0));
+    }
+
+    #[test]
+    fn round_trip() {
+        let start = Local.with_ymd_and_hms(2024, 5, 2, 9, 0, 0).unwrap();
+        let task = |id, hours, message| {
+            let start = start + chrono::Duration::hours(hours);
+            Task::closed(id, start, start + chrono::Duration::minutes(90), message)
+        };
+        let tasks = [
+            task(1, 0, "fix the parser"),
+            task(3, 2, "review"),
+            task(5, 4, "fix the parser"),
+        ];
+
+        let mut out = Vec::new();
+        Org.write(&tasks, &Config::default(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out,
+            "* 2024-05-02 Thu\n\
+             ** fix the parser\n   \
+             CLOCK: [2024-05-02 Thu 09:00]--[2024-05-02 Thu 10:30] =>  1:30\n   \
+             CLOCK: [2024-05-02 Thu 13:00]--[2024-05-02 Thu 14:30] =>  1:30\n\
+             ** review\n   \
+             CLOCK: [2024-05-02 Thu 11:00]--[2024-05-02 Thu 12:30] =>  1:30\n"
+        );
+
+        let intervals = parse(Path::new("worked.org"), &out).unwrap();
+        let messages: Vec<_> = intervals.iter().map(|i| i.message.as_str()).collect();
+        assert_eq!(messages, ["fix the parser", "fix the parser", "review"]);
+        assert_eq!(intervals[2].start, tasks[1].start.with_timezone(&Utc));
+    }
+}
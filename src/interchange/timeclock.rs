@@ -0,0 +1,68 @@
+//! The timeclock format read by ledger and hledger (`hledger -f worked.timeclock`):
+//!
+//! ```text
+//! i 2024/05/02 09:00:00 projects:worklog  fix the parser
+//! o 2024/05/02 10:30:00
+//! ```
+//!
+//! Each task is clocked in to an account, chosen by [`Timeclock::account`](crate::config::Timeclock::account).
+
+use std::io::Write;
+
+use super::{split_tags, Error, Exporter};
+use crate::{config::Config, task::Task};
+
+/// Exports tasks as timeclock entries.
+pub struct Timeclock;
+
+const TIMESTAMP: &str = "%Y/%m/%d %H:%M:%S";
+
+impl Exporter for Timeclock {
+    fn write(&self, tasks: &[Task], config: &Config, out: &mut dyn Write) -> Result<(), Error> {
+        for task in tasks {
+            let account = config.timeclock.account(&task.message);
+            let (description, _tags) = split_tags(&task.message);
+            let start = task.start.format(TIMESTAMP);
+            // account names may contain single spaces, so they're ended by two
+            writeln!(out, "i {start} {account}  {description}")?;
+            // a task still in progress is left clocked in, which hledger counts up to now
+            if let Some(stop) = task.stop {
+                let stop = stop.format(TIMESTAMP);
+                writeln!(out, "o {stop}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone as _};
+
+    use super::*;
+
+    #[test]
+    fn clock_in_and_out() {
+        let start = Local.with_ymd_and_hms(2024, 5, 2, 9, 0, 0).unwrap();
+        let tasks = [
+            Task::closed(
+                1,
+                start,
+                start + chrono::Duration::minutes(90),
+                "fix the parser +worklog",
+            ),
+            Task::running(3, start + chrono::Duration::hours(2), "lunch"),
+        ];
+
+        let mut out = Vec::new();
+        Timeclock
+            .write(&tasks, &Config::default(), &mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "i 2024/05/02 09:00:00 worklog  fix the parser\n\
+             o 2024/05/02 10:30:00\n\
+             i 2024/05/02 11:00:00 work  lunch\n"
+        );
+    }
+}
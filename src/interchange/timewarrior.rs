@@ -0,0 +1,139 @@
+//! Timewarrior keeps its data in monthly files named like `2024-01.data`, one interval per line:
+//!
+//! ```text
+//! inc 20240101T090000Z - 20240101T100000Z # tag "tag two"
+//! ```
+
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use super::{Error, Importer, Interval};
+
+/// Imports Timewarrior's data directory.
+pub struct Timewarrior;
+
+impl Importer for Timewarrior {
+    fn read(&self, path: &Path) -> Result<Vec<Interval>, Error> {
+        read_dir(path)
+    }
+}
+
+peg::parser! {
+    grammar timewarrior_parser() for str {
+        rule ws() = [' ' | '\t']
+
+        rule timestamp() -> DateTime<Utc>
+            = ts:$(['0'..='9']*<8> "T" ['0'..='9']*<6> "Z") {?
+                NaiveDateTime::parse_from_str(ts, "%Y%m%dT%H%M%SZ")
+                    .map(|naive| naive.and_utc())
+                    .map_err(|_| "timestamp")
+            }
+
+        // tags with spaces are quoted, and may contain escaped quotes
+        rule quoted() -> String
+            = "\"" chars:(("\\" c:[_] { c }) / [^ '"' | '\\'])* "\"" {
+                chars.into_iter().collect()
+            }
+        // a lone "#" separates the tags from the annotation
+        rule bare() -> String
+            = tag:$((!ws() [_])+) {?
+                if tag == "#" {
+                    Err("tag")
+                } else {
+                    Ok(tag.to_owned())
+                }
+            }
+        rule tag() -> String
+            = quoted() / bare()
+        rule tags() -> Vec<String>
+            = "#" ws()* tags:(tag() ** (ws()+)) ws()* { tags }
+
+        pub rule line() -> Interval
+            = "inc" ws()+ start:timestamp() stop:(ws()+ "-" ws()+ stop:timestamp() { stop })? ws()*
+                tags:tags()? annotation:tags()? {
+                let message = tags
+                    .into_iter()
+                    .chain(annotation)
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                Interval { start, stop, message }
+            }
+    }
+}
+
+/// Read every interval from every `*.data` file in a Timewarrior data directory.
+fn read_dir(dir: &Path) -> Result<Vec<Interval>, Error> {
+    let mut paths = std::fs::read_dir(dir)
+        .map_err(|err| Error::Read(dir.to_owned(), err))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| Error::Read(dir.to_owned(), err))?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "data"));
+    paths.sort();
+
+    let mut intervals = Vec::new();
+    for path in paths {
+        let contents =
+            std::fs::read_to_string(&path).map_err(|err| Error::Read(path.clone(), err))?;
+        for (idx, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let interval = timewarrior_parser::line(line).map_err(|source| Error::Parse {
+                path: path.clone(),
+                line: idx + 1,
+                source,
+            })?;
+            intervals.push(interval);
+        }
+    }
+
+    Ok(intervals)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone as _;
+
+    use super::*;
+
+    #[test]
+    fn closed_interval_with_tags() {
+        let interval =
+            timewarrior_parser::line(r#"inc 20240101T090000Z - 20240101T100000Z # tag "tag two""#)
+                .unwrap();
+        assert_eq!(
+            interval,
+            Interval {
+                start: Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap(),
+                stop: Some(Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap()),
+                message: "tag tag two".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn open_interval() {
+        let interval = timewarrior_parser::line("inc 20240101T090000Z # #1234").unwrap();
+        assert_eq!(interval.stop, None);
+        assert_eq!(interval.message, "#1234");
+    }
+
+    #[test]
+    fn untagged_interval() {
+        let interval = timewarrior_parser::line("inc 20240101T090000Z - 20240101T100000Z").unwrap();
+        assert_eq!(interval.message, "");
+    }
+
+    #[test]
+    fn annotation() {
+        let interval = timewarrior_parser::line(
+            r#"inc 20240101T090000Z - 20240101T100000Z # review # "looked at \"the\" thing""#,
+        )
+        .unwrap();
+        assert_eq!(interval.message, r#"review looked at "the" thing"#);
+    }
+}
@@ -0,0 +1,161 @@
+//! Toggl Track exports time entries as CSV, one row per entry, with times in the local timezone.
+//!
+//! The project, description, and tags map onto a `worklog` message as `project description +tag +tag`.
+//! Exported entries have no project, as it can't be distinguished from the description.
+
+use std::{io::Write, path::Path};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone as _, Utc};
+use serde::{Deserialize, Serialize, Serializer};
+
+use super::{join_tags, split_tags, Error, Exporter, Importer, Interval};
+use crate::{config::Config, task::Task};
+
+/// Imports and exports Toggl Track's CSV format.
+pub struct Toggl;
+
+#[derive(Deserialize, Serialize)]
+struct Row {
+    #[serde(rename = "Email", default)]
+    email: String,
+    #[serde(rename = "Project", default)]
+    project: String,
+    #[serde(rename = "Description", default)]
+    description: String,
+    #[serde(rename = "Start date")]
+    start_date: NaiveDate,
+    #[serde(rename = "Start time", serialize_with = "hms")]
+    start_time: NaiveTime,
+    #[serde(rename = "End date")]
+    end_date: NaiveDate,
+    #[serde(rename = "End time", serialize_with = "hms")]
+    end_time: NaiveTime,
+    #[serde(rename = "Duration", default)]
+    duration: String,
+    /// Comma-separated
+    #[serde(rename = "Tags", default)]
+    tags: String,
+}
+
+/// Toggl writes times to the second, without the fractional seconds `NaiveTime` would otherwise include.
+fn hms<S: Serializer>(time: &NaiveTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&time.format("%H:%M:%S"))
+}
+
+fn local(path: &Path, date: NaiveDate, time: NaiveTime) -> Result<DateTime<Utc>, Error> {
+    Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .map(Into::into)
+        .ok_or_else(|| Error::InvalidTimestamp(path.to_owned()))
+}
+
+impl Importer for Toggl {
+    fn read(&self, path: &Path) -> Result<Vec<Interval>, Error> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|err| Error::Read(path.to_owned(), err))?;
+        parse(path, &contents)
+    }
+}
+
+fn parse(path: &Path, contents: &str) -> Result<Vec<Interval>, Error> {
+    csv::Reader::from_reader(contents.as_bytes())
+        .deserialize()
+        .map(|row| {
+            let row: Row = row.map_err(|err| Error::ParseCsv(path.to_owned(), err))?;
+            Ok(Interval {
+                start: local(path, row.start_date, row.start_time)?,
+                stop: Some(local(path, row.end_date, row.end_time)?),
+                message: join_tags(
+                    [row.project.as_str(), row.description.as_str()],
+                    row.tags.split(','),
+                ),
+            })
+        })
+        .collect()
+}
+
+impl Exporter for Toggl {
+    fn write(&self, tasks: &[Task], _config: &Config, out: &mut dyn Write) -> Result<(), Error> {
+        let mut writer = csv::Writer::from_writer(out);
+        for task in tasks {
+            let Some(stop) = task.stop else {
+                continue;
+            };
+            let (description, tags) = split_tags(&task.message);
+            let seconds = (stop - task.start).num_seconds();
+            let duration = format!(
+                "{:02}:{:02}:{:02}",
+                seconds / 3600,
+                (seconds / 60) % 60,
+                seconds % 60
+            );
+            writer
+                .serialize(Row {
+                    email: String::new(),
+                    project: String::new(),
+                    description,
+                    start_date: task.start.date_naive(),
+                    start_time: task.start.time(),
+                    end_date: stop.date_naive(),
+                    end_time: stop.time(),
+                    duration,
+                    tags: tags.join(", "),
+                })
+                .map_err(Error::WriteCsv)?;
+        }
+        writer.flush().map_err(|err| Error::WriteCsv(err.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Timelike as _};
+
+    use super::*;
+    use crate::interchange::tests::{logged, reimport};
+
+    #[test]
+    fn entry_with_tags() {
+        let intervals = parse(
+            Path::new("toggl.csv"),
+            "Email,Project,Description,Start date,Start time,End date,End time,Duration,Tags\n\
+             me@example.com,worklog,review,2024-01-01,09:00:00,2024-01-01,10:30:00,01:30:00,\"#1234, urgent\"\n",
+        )
+        .unwrap();
+        let start = Local.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap();
+        assert_eq!(
+            intervals,
+            [Interval {
+                start: start.into(),
+                stop: Some((start + Duration::minutes(90)).into()),
+                message: "worklog review +#1234 +urgent".into(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        // events are recorded to the millisecond, but Toggl only deals in whole seconds
+        let start =
+            Local.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap() + Duration::milliseconds(250);
+        let (mut conn, task) = logged(
+            start.into(),
+            (start + Duration::minutes(90)).into(),
+            "worklog +review",
+        )
+        .await;
+
+        let mut out = Vec::new();
+        Toggl.write(&[task], &Config::default(), &mut out).unwrap();
+        let csv = std::str::from_utf8(&out).unwrap();
+        assert!(csv.contains(",09:00:00,"), "{csv}");
+        assert!(csv.contains(",10:30:00,"), "{csv}");
+
+        let intervals = parse(Path::new("toggl.csv"), csv).unwrap();
+        assert_eq!(intervals.len(), 1);
+        assert_eq!(intervals[0].start, start.with_nanosecond(0).unwrap());
+        assert_eq!(intervals[0].message, "worklog +review");
+        reimport(&mut conn, intervals).await;
+    }
+}
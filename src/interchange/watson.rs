@@ -0,0 +1,115 @@
+//! Watson keeps its data in a single JSON file named `frames`, holding an array of frames:
+//!
+//! ```text
+//! [1704099600, 1704103200, "project", "0123456789abcdef0123456789abcdef", ["tag", "tag two"], 1704103200]
+//! ```
+//!
+//! Those are the start, stop, project, id, tags, and last update time. Timestamps are in Unix seconds.
+//! The project and tags map onto a `worklog` message as `project +tag +tag`.
+
+use std::{io::Write, path::Path};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{join_tags, split_tags, Error, Exporter, Importer, Interval};
+use crate::{config::Config, task::Task};
+
+/// Imports and exports Watson's `frames` file.
+pub struct Watson;
+
+#[derive(Deserialize, Serialize)]
+struct Frame(i64, i64, String, String, Vec<String>, i64);
+
+impl Importer for Watson {
+    fn read(&self, path: &Path) -> Result<Vec<Interval>, Error> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|err| Error::Read(path.to_owned(), err))?;
+        parse(path, &contents)
+    }
+}
+
+fn parse(path: &Path, contents: &str) -> Result<Vec<Interval>, Error> {
+    let frames: Vec<Frame> =
+        serde_json::from_str(contents).map_err(|err| Error::ParseJson(path.to_owned(), err))?;
+
+    frames
+        .into_iter()
+        .map(|Frame(start, stop, project, _id, tags, _updated_at)| {
+            let timestamp = |secs| {
+                DateTime::<Utc>::from_timestamp(secs, 0)
+                    .ok_or_else(|| Error::InvalidTimestamp(path.to_owned()))
+            };
+            Ok(Interval {
+                start: timestamp(start)?,
+                stop: Some(timestamp(stop)?),
+                message: join_tags([project.as_str()], tags.iter().map(String::as_str)),
+            })
+        })
+        .collect()
+}
+
+impl Exporter for Watson {
+    fn write(&self, tasks: &[Task], _config: &Config, out: &mut dyn Write) -> Result<(), Error> {
+        let frames: Vec<_> = tasks
+            .iter()
+            .filter_map(|task| {
+                let stop = task.stop?.timestamp();
+                let (project, tags) = split_tags(&task.message);
+                // watson ids are hex uuids; the START id is stable, so derive from that
+                let id = format!("{:032x}", task.id);
+                Some(Frame(task.start.timestamp(), stop, project, id, tags, stop))
+            })
+            .collect();
+
+        serde_json::to_writer_pretty(&mut *out, &frames).map_err(Error::WriteJson)?;
+        writeln!(out)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone as _;
+
+    use super::*;
+    use crate::interchange::tests::{logged, reimport};
+
+    #[test]
+    fn frame_with_tags() {
+        let intervals = parse(
+            Path::new("frames"),
+            r##"[[1704099600, 1704103200, "worklog", "0123456789abcdef0123456789abcdef", ["review", "#1234"], 1704103200]]"##,
+        )
+        .unwrap();
+        assert_eq!(
+            intervals,
+            [Interval {
+                start: Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap(),
+                stop: Some(Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap()),
+                message: "worklog +review +#1234".into(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        // events are recorded to the millisecond, but Watson only deals in whole seconds
+        let second = Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap();
+        let start = second + chrono::Duration::milliseconds(250);
+        let (mut conn, task) = logged(
+            start,
+            start + chrono::Duration::minutes(90),
+            "worklog +review",
+        )
+        .await;
+
+        let mut out = Vec::new();
+        Watson.write(&[task], &Config::default(), &mut out).unwrap();
+        let intervals = parse(Path::new("frames"), std::str::from_utf8(&out).unwrap()).unwrap();
+        assert_eq!(intervals.len(), 1);
+        assert_eq!(intervals[0].start, second);
+        assert_eq!(intervals[0].message, "worklog +review");
+        reimport(&mut conn, intervals).await;
+    }
+}
@@ -1,3 +1,19 @@
 pub mod action;
+pub mod backup;
+pub mod config;
+#[cfg(unix)]
+pub mod daemon;
 pub mod db;
+pub mod doctor;
+pub mod edit;
+pub mod git;
+pub mod idle;
+pub mod interact;
+pub mod interchange;
+pub mod notify;
 pub mod paths;
+pub mod picker;
+pub mod server;
+pub mod state;
+pub mod task;
+pub mod tui;
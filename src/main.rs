@@ -1,18 +1,103 @@
-use worklog::{action::Action, db};
+use color_eyre::eyre::Result;
+use worklog::{
+    action::{self, Action, Options},
+    config::Config,
+    db::{Db, Overlap},
+    interact,
+};
 
 mod cli;
-use crate::cli::Cli;
+use crate::cli::{Cli, Flags};
 
 #[tokio::main]
-async fn main() -> color_eyre::eyre::Result<()> {
+async fn main() -> Result<()> {
     color_eyre::install()?;
 
-    let args: Vec<_> = std::env::args().skip(1).collect();
+    let (flags, args) = Flags::extract(std::env::args().skip(1));
     let args = args.join(" ");
     let action: Action = Cli::parse(&args)?.into();
 
-    let mut conn = db::establish_connection().await?;
-    action.execute(&mut conn).await?;
+    let mut options = Options {
+        config: load_config(&action)?,
+        ..Options::default()
+    };
+    if flags.force {
+        options.overlap = Overlap::Allow;
+    }
+
+    // the database is only opened once the action needs it
+    let mut db = Db::lazy(action.access());
+    match run(action.clone(), &mut db, &options).await {
+        Err(err) if reshapes_history(&err) && !flags.strict => {
+            eprintln!("{err}");
+            if interact::confirm("insert anyway?")? {
+                options.overlap = Overlap::Allow;
+                run(action, &mut db, &options).await?;
+            } else {
+                eprintln!("nothing inserted");
+            }
+        }
+        result => result?,
+    }
 
     Ok(())
 }
+
+/// Load the configuration.
+///
+/// A broken config file doesn't stop the actions which help find and fix it; they warn, and use the defaults.
+fn load_config(action: &Action) -> Result<Config> {
+    let finds_config = matches!(
+        action,
+        Action::Help(_) | Action::PathConfig | Action::PathDatabase
+    );
+    match Config::load() {
+        Err(err) if finds_config => {
+            let err = color_eyre::eyre::Report::from(err);
+            eprintln!("warning: ignoring the config file: {err:#}");
+            Ok(Config::default())
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Execute the action, delegating it to the daemon if one is running.
+async fn run(action: Action, db: &mut Db, options: &Options) -> Result<()> {
+    #[cfg(unix)]
+    if action.delegable() {
+        if let Some(mut client) = worklog::daemon::Client::connect().await {
+            let output = client.execute(action, options).await?;
+            print!("{output}");
+            return Ok(());
+        }
+    }
+
+    let mutates = action.mutates();
+    action
+        .execute(db, options, true, &mut std::io::stdout())
+        .await?;
+
+    // let the daemon's subscribers know about changes it didn't make itself
+    #[cfg(unix)]
+    if mutates {
+        if let Some(mut client) = worklog::daemon::Client::connect().await {
+            client.notify_changed().await?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = mutates;
+
+    Ok(())
+}
+
+/// Whether an action was refused because it would split or truncate existing tasks.
+fn reshapes_history(err: &color_eyre::eyre::Report) -> bool {
+    #[cfg(unix)]
+    if let Some(worklog::daemon::Error::Remote {
+        reshapes_history, ..
+    }) = err.downcast_ref()
+    {
+        return *reshapes_history;
+    }
+    matches!(err.downcast_ref(), Some(action::Error::ReshapesHistory(_)))
+}
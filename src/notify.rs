@@ -0,0 +1,267 @@
+//! Desktop notifications about the task in progress, and about the lack of one.
+
+use std::process::Command;
+
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone as _, Utc};
+
+use crate::{
+    config::{self, Workday},
+    state::CurrentTask,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub summary: String,
+    pub body: String,
+}
+
+/// How to deliver notifications.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sender {
+    /// A command which is given the summary and body as arguments, like `notify-send`.
+    Command(String),
+    /// The freedesktop notification service on the session bus.
+    DBus,
+}
+
+impl From<&config::Notify> for Sender {
+    fn from(config: &config::Notify) -> Self {
+        match &config.command {
+            Some(command) => Self::Command(command.clone()),
+            None => Self::DBus,
+        }
+    }
+}
+
+impl Sender {
+    pub fn send(&self, notification: &Notification) -> Result<(), Error> {
+        let Notification { summary, body } = notification;
+        let mut command = match self {
+            Self::Command(command) => {
+                let mut sh = Command::new("sh");
+                // `$0` names the script; the rest become `$@`
+                sh.arg("-c")
+                    .arg(format!("{command} \"$@\""))
+                    .args(["worklog", summary, body]);
+                sh
+            }
+            Self::DBus => {
+                let mut gdbus = Command::new("gdbus");
+                gdbus
+                    .args([
+                        "call",
+                        "--session",
+                        "--dest=org.freedesktop.Notifications",
+                        "--object-path=/org/freedesktop/Notifications",
+                        "--method=org.freedesktop.Notifications.Notify",
+                        "'worklog'",
+                        "0",
+                        "''",
+                    ])
+                    .arg(gvariant_string(summary))
+                    .arg(gvariant_string(body))
+                    .args(["[]", "{}", "-1"]);
+                gdbus
+            }
+        };
+
+        let program = command.get_program().to_string_lossy().into_owned();
+        let output = command
+            .output()
+            .map_err(|err| Error::Run(program.clone(), err))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+            return Err(Error::Failed(program, stderr));
+        }
+        Ok(())
+    }
+}
+
+/// Quote a string in the GVariant text format, as `gdbus` expects its arguments.
+fn gvariant_string(s: &str) -> String {
+    let escaped = s.replace('\\', r"\\").replace('\'', r"\'");
+    format!("'{escaped}'")
+}
+
+/// Which reminders have already been sent, so that each is only sent once.
+#[derive(Debug, Default)]
+pub struct Reminders {
+    /// The task which was reported as running long.
+    long_task: Option<i64>,
+    /// The start of the gap which was reported as having nothing in progress.
+    nothing_since: Option<DateTime<Utc>>,
+}
+
+impl Reminders {
+    /// Remind about the task in progress once it's run for `limit`.
+    pub fn long_task(
+        &mut self,
+        task: Option<&CurrentTask>,
+        limit: Duration,
+        now: DateTime<Utc>,
+    ) -> Option<Notification> {
+        let task = task.filter(|task| now - task.start >= limit)?;
+        if self.long_task == Some(task.id) {
+            return None;
+        }
+        self.long_task = Some(task.id);
+        Some(Notification {
+            summary: format!(
+                "#{} has been running for {}",
+                task.id,
+                hours_minutes(now - task.start)
+            ),
+            body: task.message.clone(),
+        })
+    }
+
+    /// Remind that nothing is in progress once nothing has been for `after`, during working hours.
+    ///
+    /// `stopped` is when the last task stopped, if any ever did. Working hours run from the start of the
+    /// workday to its end, or to midnight without one; without a start, there are no working hours.
+    pub fn nothing_running(
+        &mut self,
+        stopped: Option<DateTime<Utc>>,
+        workday: &Workday,
+        after: Duration,
+        now: DateTime<Local>,
+    ) -> Option<Notification> {
+        let start = today_at(workday.start?, now)?;
+        let end = workday.end.and_then(|end| today_at(end, now));
+        if now < start || end.is_some_and(|end| now >= end) {
+            return None;
+        }
+
+        let since = stopped.map_or(start, |stopped| stopped.with_timezone(&Local).max(start));
+        if now - since < after {
+            return None;
+        }
+        let since = since.to_utc();
+        if self.nothing_since == Some(since) {
+            return None;
+        }
+        self.nothing_since = Some(since);
+        Some(Notification {
+            summary: "nothing in progress".into(),
+            body: format!(
+                "no task has been running since {}",
+                since.with_timezone(&Local).format("%H:%M")
+            ),
+        })
+    }
+}
+
+/// Whether the time of day `time` came after `previous`, and no later than `now`.
+pub fn crossed(time: NaiveTime, previous: DateTime<Local>, now: DateTime<Local>) -> bool {
+    today_at(time, now).is_some_and(|at| previous < at && at <= now)
+}
+
+fn today_at(time: NaiveTime, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    Local
+        .from_local_datetime(&now.date_naive().and_time(time))
+        .earliest()
+}
+
+fn hours_minutes(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("running {0}")]
+    Run(String, #[source] std::io::Error),
+    #[error("{0} failed: {1}")]
+    Failed(String, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(time: &str) -> DateTime<Local> {
+        let time = NaiveTime::parse_from_str(time, "%H:%M").unwrap();
+        today_at(time, Local::now()).unwrap()
+    }
+
+    fn workday() -> Workday {
+        Workday {
+            start: NaiveTime::from_hms_opt(9, 0, 0),
+            end: NaiveTime::from_hms_opt(17, 0, 0),
+            max_task_length: None,
+        }
+    }
+
+    #[test]
+    fn quotes_gvariant_strings() {
+        assert_eq!(gvariant_string(r"it's a \ "), r"'it\'s a \\ '");
+    }
+
+    #[test]
+    fn long_task_once() {
+        let task = CurrentTask {
+            id: 3,
+            message: "#12".into(),
+            start: local("09:00").to_utc(),
+            scheduled_stop: None,
+        };
+        let mut reminders = Reminders::default();
+        let limit = Duration::hours(2);
+        assert_eq!(
+            reminders.long_task(Some(&task), limit, local("10:30").to_utc()),
+            None
+        );
+        assert_eq!(
+            reminders.long_task(Some(&task), limit, local("11:05").to_utc()),
+            Some(Notification {
+                summary: "#3 has been running for 2:05".into(),
+                body: "#12".into(),
+            })
+        );
+        assert_eq!(
+            reminders.long_task(Some(&task), limit, local("11:30").to_utc()),
+            None
+        );
+    }
+
+    #[test]
+    fn nothing_running_during_working_hours() {
+        let mut reminders = Reminders::default();
+        let after = Duration::minutes(15);
+        let workday = workday();
+
+        // before work, and not long after it starts
+        assert!(reminders
+            .nothing_running(None, &workday, after, local("08:00"))
+            .is_none());
+        assert!(reminders
+            .nothing_running(None, &workday, after, local("09:10"))
+            .is_none());
+        // nothing since work started
+        let reminder = reminders
+            .nothing_running(None, &workday, after, local("09:20"))
+            .unwrap();
+        assert_eq!(reminder.body, "no task has been running since 09:00");
+        assert!(reminders
+            .nothing_running(None, &workday, after, local("09:30"))
+            .is_none());
+        // a new gap after a task stopped
+        let stopped = Some(local("12:00").to_utc());
+        assert!(reminders
+            .nothing_running(stopped, &workday, after, local("12:15"))
+            .is_some());
+        // after work
+        let stopped = Some(local("16:00").to_utc());
+        assert!(reminders
+            .nothing_running(stopped, &workday, after, local("17:30"))
+            .is_none());
+    }
+
+    #[test]
+    fn crossing_a_time() {
+        let end = NaiveTime::from_hms_opt(17, 0, 0).unwrap();
+        assert!(crossed(end, local("16:59"), local("17:00")));
+        assert!(!crossed(end, local("17:00"), local("17:01")));
+        assert!(!crossed(end, local("16:00"), local("16:30")));
+    }
+}
@@ -13,3 +13,22 @@ pub fn config() -> PathBuf {
         .join("worklog")
         .join("config.toml")
 }
+
+/// The file caching the task in progress; see [`crate::state`].
+pub fn state() -> PathBuf {
+    database().with_file_name("state.json")
+}
+
+/// The Unix socket on which the daemon listens.
+pub fn socket() -> PathBuf {
+    dirs::runtime_dir()
+        .map(|dir| dir.join("worklog"))
+        .unwrap_or_else(|| {
+            let database = database();
+            database
+                .parent()
+                .expect("DB path is never the root")
+                .to_owned()
+        })
+        .join("daemon.sock")
+}
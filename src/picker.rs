@@ -0,0 +1,310 @@
+//! A fuzzy finder over recent task messages, for starting a task without retyping its message.
+//!
+//! Messages are ranked by frecency: each time a message was used counts for less the longer ago it was.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Style, Stylize as _},
+    text::Line,
+    widgets::{List, ListItem, ListState, Paragraph},
+    Frame, TerminalOptions, Viewport,
+};
+use sqlx::SqliteConnection;
+
+use crate::db::{self, EvtType, RetrieveEvent};
+
+/// How many candidates are shown at once.
+const SHOWN: u16 = 10;
+/// A use of a message counts for half as much after this long.
+const HALF_LIFE_HOURS: f64 = 72.0;
+
+/// A message which has been used recently, and how highly it ranks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub message: String,
+    pub frecency: f64,
+}
+
+/// The distinct messages of tasks started since `since`, most frecent first.
+pub async fn recent(
+    conn: &mut SqliteConnection,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<Vec<Candidate>, Error> {
+    let uses = RetrieveEvent::events_between(conn, since, now)
+        .await?
+        .into_iter()
+        .filter(|event| matches!(event.evt_type, EvtType::Start) && !event.message.is_empty())
+        .map(|event| (event.message, event.timestamp));
+    Ok(rank(uses, now))
+}
+
+/// Rank messages by frecency, given each time one was used.
+pub fn rank(
+    uses: impl IntoIterator<Item = (String, DateTime<Utc>)>,
+    now: DateTime<Utc>,
+) -> Vec<Candidate> {
+    let mut frecencies = HashMap::<String, f64>::new();
+    for (message, used) in uses {
+        let hours = (now - used).max(Duration::zero()).num_minutes() as f64 / 60.0;
+        *frecencies.entry(message).or_default() += 0.5_f64.powf(hours / HALF_LIFE_HOURS);
+    }
+
+    let mut candidates = frecencies
+        .into_iter()
+        .map(|(message, frecency)| Candidate { message, frecency })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| {
+        b.frecency
+            .total_cmp(&a.frecency)
+            .then_with(|| a.message.cmp(&b.message))
+    });
+    candidates
+}
+
+/// How well `query` matches `message`, if it matches at all.
+///
+/// Every character of the query must appear in the message, in order, ignoring case.
+/// Consecutive characters, and characters at the start of a word, score higher.
+fn fuzzy_score(query: &str, message: &str) -> Option<u32> {
+    let mut score = 0;
+    let mut message = message.chars().flat_map(char::to_lowercase);
+    let mut previous = None;
+    let mut consecutive = false;
+
+    for wanted in query.chars().flat_map(char::to_lowercase) {
+        loop {
+            let c = message.next()?;
+            let word_start = previous.is_none_or(|p: char| !p.is_alphanumeric());
+            previous = Some(c);
+            if c == wanted {
+                score += 1 + u32::from(consecutive) * 2 + u32::from(word_start) * 3;
+                consecutive = true;
+                break;
+            }
+            consecutive = false;
+        }
+    }
+    Some(score)
+}
+
+/// The candidates matching `query`, best first.
+///
+/// Better matches rank higher; among equally good matches, more frecent messages do.
+fn matching<'a>(candidates: &'a [Candidate], query: &str) -> Vec<&'a Candidate> {
+    let mut matches = candidates
+        .iter()
+        .filter_map(|candidate| Some((fuzzy_score(query, &candidate.message)?, candidate)))
+        .collect::<Vec<_>>();
+    // the sort is stable, so candidates stay in order of frecency within a score
+    matches.sort_by(|(a, _), (b, _)| b.cmp(a));
+    matches
+        .into_iter()
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// The state of the finder.
+struct Picker<'a> {
+    candidates: &'a [Candidate],
+    query: String,
+    list: ListState,
+}
+
+/// What to do after a key press.
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Continue,
+    Cancel,
+    Pick(String),
+}
+
+impl<'a> Picker<'a> {
+    fn new(candidates: &'a [Candidate]) -> Self {
+        Self {
+            candidates,
+            query: String::new(),
+            list: ListState::default().with_selected(Some(0)),
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Outcome {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Outcome::Cancel,
+            KeyCode::Char('c') if ctrl => return Outcome::Cancel,
+            KeyCode::Down | KeyCode::Tab => self.list.select_next(),
+            KeyCode::Char('n') if ctrl => self.list.select_next(),
+            KeyCode::Up | KeyCode::BackTab => self.list.select_previous(),
+            KeyCode::Char('p') if ctrl => self.list.select_previous(),
+            KeyCode::Enter => {
+                let matches = matching(self.candidates, &self.query);
+                let selected = self.list.selected().unwrap_or_default();
+                // with nothing matching, the query becomes the message
+                return match matches.get(selected.min(matches.len().saturating_sub(1))) {
+                    Some(candidate) => Outcome::Pick(candidate.message.clone()),
+                    None if !self.query.trim().is_empty() => {
+                        Outcome::Pick(self.query.trim().to_owned())
+                    }
+                    None => Outcome::Cancel,
+                };
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.list.select_first();
+            }
+            KeyCode::Char(c) => {
+                self.query.push(c);
+                self.list.select_first();
+            }
+            _ => {}
+        }
+        Outcome::Continue
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [input, list] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(frame.area());
+        let matches = matching(self.candidates, &self.query);
+
+        let prompt = format!(
+            "start> {}█  {}/{}",
+            self.query,
+            matches.len(),
+            self.candidates.len()
+        );
+        frame.render_widget(Paragraph::new(Line::from(prompt).bold()), input);
+
+        let items = matches
+            .iter()
+            .map(|candidate| ListItem::new(candidate.message.as_str()));
+        let list_widget = List::new(items)
+            .highlight_style(Style::new().reversed())
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(list_widget, list, &mut self.list);
+    }
+}
+
+/// Let the user pick one of `candidates`, or type a new message.
+///
+/// Return `None` if they cancel.
+pub fn pick(candidates: &[Candidate]) -> Result<Option<String>, Error> {
+    let mut terminal = ratatui::try_init_with_options(TerminalOptions {
+        viewport: Viewport::Inline(SHOWN + 1),
+    })?;
+    let mut picker = Picker::new(candidates);
+
+    let result = (|| loop {
+        terminal.draw(|frame| picker.draw(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match picker.handle_key(key) {
+                Outcome::Continue => {}
+                Outcome::Cancel => return Ok(None),
+                Outcome::Pick(message) => return Ok(Some(message)),
+            }
+        }
+    })();
+
+    // leave the terminal as it was, without the finder in the way of the output
+    terminal.clear()?;
+    ratatui::restore();
+    result
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("drawing the picker")]
+    Terminal(#[from] std::io::Error),
+    #[error(transparent)]
+    Db(#[from] db::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        format!("2024-05-02T{time}:00Z").parse().unwrap()
+    }
+
+    fn candidates(messages: &[&str]) -> Vec<Candidate> {
+        messages
+            .iter()
+            .map(|message| Candidate {
+                message: (*message).into(),
+                frecency: 1.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frequent_beats_recent() {
+        let now = at("18:00");
+        let mut uses = vec![("#12 review".to_owned(), at("17:00"))];
+        for time in ["09:00", "11:00", "14:00"] {
+            uses.push(("#34 feature".into(), at(time)));
+        }
+        let ranked = rank(uses, now);
+        assert_eq!(ranked[0].message, "#34 feature");
+        assert_eq!(ranked[1].message, "#12 review");
+    }
+
+    #[test]
+    fn recent_beats_old() {
+        let now = at("18:00");
+        let uses = [
+            ("old".to_owned(), now - Duration::days(20)),
+            ("new".to_owned(), now - Duration::days(1)),
+        ];
+        let ranked = rank(uses, now);
+        assert_eq!(ranked[0].message, "new");
+        assert!(ranked[0].frecency > 10.0 * ranked[1].frecency);
+    }
+
+    #[test]
+    fn fuzzy_matching() {
+        assert!(fuzzy_score("rvw", "#12 review").is_some());
+        assert!(fuzzy_score("REV", "#12 review").is_some());
+        assert!(fuzzy_score("wer", "#12 review").is_none());
+        // word starts and runs of characters beat scattered matches
+        assert!(fuzzy_score("rev", "#12 review") > fuzzy_score("rev", "three vowels"));
+    }
+
+    #[test]
+    fn better_matches_first() {
+        let candidates = candidates(&["three vowels", "#12 review", "standup"]);
+        let matches = matching(&candidates, "rev")
+            .into_iter()
+            .map(|candidate| candidate.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(matches, ["#12 review", "three vowels"]);
+    }
+
+    #[test]
+    fn pick_selected_or_typed() {
+        let candidates = candidates(&["#12 review", "standup"]);
+        let mut picker = Picker::new(&candidates);
+        picker.handle_key(KeyCode::Down.into());
+        assert_eq!(
+            picker.handle_key(KeyCode::Enter.into()),
+            Outcome::Pick("standup".into())
+        );
+
+        let mut picker = Picker::new(&candidates);
+        for c in "lunch".chars() {
+            picker.handle_key(KeyCode::Char(c).into());
+        }
+        assert_eq!(
+            picker.handle_key(KeyCode::Enter.into()),
+            Outcome::Pick("lunch".into())
+        );
+        assert_eq!(picker.handle_key(KeyCode::Esc.into()), Outcome::Cancel);
+    }
+}
@@ -0,0 +1,544 @@
+//! A local HTTP server exposing the event log as JSON, for dashboards and other tools.
+//!
+//! Events are represented as in a [backup](crate::backup), and the task in progress as in the
+//! [state file](crate::state).
+
+use std::{future::Future, net::SocketAddr};
+
+use axum::{
+    extract::{Path, Query, State as Extract},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    action::{self, Options, Period},
+    backup::BackupEvent,
+    db::{self, Access, Db, EvtType, Id, InsertEvent, Overlap, RetrieveEvent, UpdateEvent},
+    edit,
+    state::{self, State},
+    task::Task,
+};
+
+/// A request for the worker which owns the database.
+enum Command {
+    Status,
+    ListEvents(Period),
+    GetEvent(Id),
+    /// Insert an event, even if it reshapes history when forced.
+    InsertEvent(NewEvent, bool),
+    /// Update an event, even if it moves past the events on either side when forced.
+    UpdateEvent(Id, EventChanges, bool),
+    DeleteEvent(Id),
+    Report(Period),
+}
+
+type Job = (Command, oneshot::Sender<Response>);
+
+/// Hands requests to the worker, and waits for its responses.
+#[derive(Clone)]
+struct Jobs(mpsc::Sender<Job>);
+
+impl Jobs {
+    async fn run(&self, command: Command) -> Response {
+        let (respond, response) = oneshot::channel();
+        if self.0.send((command, respond)).await.is_err() {
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+        response
+            .await
+            .unwrap_or_else(|_| StatusCode::SERVICE_UNAVAILABLE.into_response())
+    }
+}
+
+/// The days to query, as `?from=2024-05-01&to=2024-05-07`.
+///
+/// Both days are inclusive. Without `from`, the range starts today; without `to`, it ends on `from`.
+#[derive(Debug, Default, Deserialize)]
+struct Range {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+impl From<Range> for Period {
+    fn from(range: Range) -> Self {
+        let first = range.from.unwrap_or_else(|| Local::now().date_naive());
+        Self {
+            first,
+            last: range.to.unwrap_or(first),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Force {
+    /// Insert the event even if it splits or truncates existing tasks, or move it past its neighbours.
+    #[serde(default)]
+    force: bool,
+}
+
+/// The body of a request to insert an event.
+#[derive(Debug, Deserialize)]
+struct NewEvent {
+    evt_type: EvtType,
+    timestamp: DateTime<Utc>,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    provisional: bool,
+}
+
+/// The body of a request to update an event; absent fields are left alone.
+#[derive(Debug, Deserialize)]
+struct EventChanges {
+    timestamp: Option<DateTime<Utc>>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ReportBody {
+    tasks: Vec<Task>,
+    /// The total duration of the finished tasks.
+    total_seconds: i64,
+}
+
+/// The REST API over a database, and the worker which executes its requests.
+///
+/// Database futures can't move between threads, so requests are executed one at a time by the worker,
+/// which must be polled alongside the router. If `refresh_state` is set, the worker rewrites the state
+/// file after every change, as actions do.
+pub fn app(db: Db, options: Options, refresh_state: bool) -> (Router, impl Future<Output = ()>) {
+    let (send, receive) = mpsc::channel(16);
+    let router = Router::new()
+        .route("/status", get(status))
+        .route("/events", get(list_events).post(insert_event))
+        .route(
+            "/events/{id}",
+            get(get_event).patch(update_event).delete(delete_event),
+        )
+        .route("/report", get(report))
+        .with_state(Jobs(send));
+    (router, work(db, options, refresh_state, receive))
+}
+
+/// Serve the REST API on `listen` until interrupted.
+pub async fn serve(listen: SocketAddr, options: Options) -> Result<(), Error> {
+    let mut db = Db::lazy(Access::ReadWrite);
+    db.conn().await?;
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .map_err(|err| Error::Bind(listen, err))?;
+    let (router, worker) = app(db, options, true);
+    eprintln!("listening on http://{listen}");
+
+    let server = axum::serve(listener, router).with_graceful_shutdown(async {
+        let _ = tokio::signal::ctrl_c().await;
+    });
+    tokio::select! {
+        result = server => result.map_err(Error::Serve),
+        () = worker => Ok(()),
+    }
+}
+
+async fn status(Extract(jobs): Extract<Jobs>) -> Response {
+    jobs.run(Command::Status).await
+}
+
+async fn list_events(Extract(jobs): Extract<Jobs>, Query(range): Query<Range>) -> Response {
+    jobs.run(Command::ListEvents(range.into())).await
+}
+
+async fn get_event(Extract(jobs): Extract<Jobs>, Path(id): Path<Id>) -> Response {
+    jobs.run(Command::GetEvent(id)).await
+}
+
+async fn insert_event(
+    Extract(jobs): Extract<Jobs>,
+    Query(force): Query<Force>,
+    Json(event): Json<NewEvent>,
+) -> Response {
+    jobs.run(Command::InsertEvent(event, force.force)).await
+}
+
+async fn update_event(
+    Extract(jobs): Extract<Jobs>,
+    Path(id): Path<Id>,
+    Query(force): Query<Force>,
+    Json(changes): Json<EventChanges>,
+) -> Response {
+    jobs.run(Command::UpdateEvent(id, changes, force.force))
+        .await
+}
+
+async fn delete_event(Extract(jobs): Extract<Jobs>, Path(id): Path<Id>) -> Response {
+    jobs.run(Command::DeleteEvent(id)).await
+}
+
+async fn report(Extract(jobs): Extract<Jobs>, Query(range): Query<Range>) -> Response {
+    jobs.run(Command::Report(range.into())).await
+}
+
+/// Execute requests until the router is dropped.
+async fn work(mut db: Db, options: Options, refresh_state: bool, mut jobs: mpsc::Receiver<Job>) {
+    while let Some((command, respond)) = jobs.recv().await {
+        let mutates = matches!(
+            command,
+            Command::InsertEvent(..) | Command::UpdateEvent(..) | Command::DeleteEvent(_)
+        );
+        let mut response = execute(&mut db, &options, command).await;
+        if mutates && refresh_state && response.status().is_success() {
+            if let Err(err) = refresh(&mut db).await {
+                response = err.into_response();
+            }
+        }
+        // the client may have gone away; that's fine
+        let _ = respond.send(response);
+    }
+}
+
+/// Keep the state file in step with the database.
+async fn refresh(db: &mut Db) -> Result<(), Error> {
+    State::refresh(db.conn().await?).await?;
+    Ok(())
+}
+
+async fn execute(db: &mut Db, options: &Options, command: Command) -> Response {
+    let result = async {
+        let conn = db.conn().await?;
+        let response = match command {
+            Command::Status => Json(State::query(conn).await?).into_response(),
+            Command::ListEvents(period) => {
+                let (start, end) = period.bounds()?;
+                let events = RetrieveEvent::events_between(conn, start, end).await?;
+                Json(
+                    events
+                        .into_iter()
+                        .map(BackupEvent::from)
+                        .collect::<Vec<_>>(),
+                )
+                .into_response()
+            }
+            Command::GetEvent(id) => {
+                let event = RetrieveEvent::by_id(conn, id)
+                    .await?
+                    .ok_or(Error::NotFound(id))?;
+                Json(BackupEvent::from(event)).into_response()
+            }
+            Command::InsertEvent(event, force) => {
+                let overlap = if force {
+                    Overlap::Allow
+                } else {
+                    options.overlap
+                };
+                let event = insert(conn, event, overlap).await?;
+                (StatusCode::CREATED, Json(event)).into_response()
+            }
+            Command::UpdateEvent(id, changes, force) => {
+                let overlap = if force {
+                    Overlap::Allow
+                } else {
+                    options.overlap
+                };
+                // moving an event past its neighbours would reorder history
+                if let Some(to) = changes.timestamp.filter(|_| overlap == Overlap::Refuse) {
+                    let event = RetrieveEvent::by_id(conn, id)
+                        .await?
+                        .ok_or(Error::NotFound(id))?;
+                    edit::keep_order(conn, &event, to).await?;
+                }
+                let updated = UpdateEvent {
+                    id,
+                    timestamp: changes.timestamp,
+                    message: changes.message,
+                }
+                .update(conn)
+                .await?;
+                let event = RetrieveEvent::by_id(conn, id)
+                    .await?
+                    .filter(|_| updated)
+                    .ok_or(Error::NotFound(id))?;
+                Json(BackupEvent::from(event)).into_response()
+            }
+            Command::DeleteEvent(id) => {
+                if !db::delete_event(conn, id).await? {
+                    return Err(Error::NotFound(id));
+                }
+                StatusCode::NO_CONTENT.into_response()
+            }
+            Command::Report(period) => {
+                let (tasks, _) = action::tasks_in(conn, options, period).await?;
+                let total_seconds = action::total_duration(&tasks).num_seconds();
+                Json(ReportBody {
+                    tasks,
+                    total_seconds,
+                })
+                .into_response()
+            }
+        };
+        Ok::<_, Error>(response)
+    }
+    .await;
+    result.unwrap_or_else(IntoResponse::into_response)
+}
+
+async fn insert(
+    conn: &mut SqliteConnection,
+    event: NewEvent,
+    overlap: Overlap,
+) -> Result<BackupEvent, Error> {
+    let NewEvent {
+        evt_type,
+        timestamp,
+        message,
+        provisional,
+    } = event;
+    let id = InsertEvent {
+        evt_type,
+        timestamp,
+        message: message.clone(),
+        provisional,
+    }
+    .insert(conn, overlap)
+    .await
+    .map_err(|err| match err {
+        db::Error::ReshapesHistory(starts) => {
+            Error::ReshapesHistory(starts.iter().map(|start| start.id).collect())
+        }
+        err => err.into(),
+    })?;
+    Ok(BackupEvent {
+        id,
+        evt_type,
+        timestamp,
+        message,
+        provisional,
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("binding to {0}")]
+    Bind(SocketAddr, #[source] std::io::Error),
+    #[error("serving http")]
+    Serve(#[source] std::io::Error),
+    #[error("no event with id {0}")]
+    NotFound(Id),
+    #[error("this would split or truncate existing tasks")]
+    ReshapesHistory(Vec<Id>),
+    #[error(transparent)]
+    Action(#[from] action::Error),
+    #[error(transparent)]
+    Edit(#[from] edit::Error),
+    #[error(transparent)]
+    State(#[from] state::Error),
+    #[error(transparent)]
+    Db(#[from] db::Error),
+}
+
+/// The body of an error response.
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    error: String,
+    /// The ids of the tasks an insertion would have reshaped.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    reshapes: Vec<Id>,
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::ReshapesHistory(_) | Self::Edit(edit::Error::WouldReorder(_)) => {
+                StatusCode::CONFLICT
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let reshapes = match &self {
+            Self::ReshapesHistory(ids) => ids.clone(),
+            _ => Vec::new(),
+        };
+
+        let mut error = self.to_string();
+        let mut source = std::error::Error::source(&self);
+        while let Some(err) = source {
+            error.push_str(": ");
+            error.push_str(&err.to_string());
+            source = err.source();
+        }
+        (status, Json(ErrorBody { error, reshapes })).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use http_body_util::BodyExt as _;
+    use serde_json::{json, Value};
+    use sqlx::Connection as _;
+    use tower::ServiceExt as _;
+
+    use super::*;
+    use crate::db::MIGRATOR;
+
+    /// A task from 11:00 to 12:30 UTC on 2024-05-02, served from an in-memory database.
+    async fn fixture() -> (Router, impl Future<Output = ()>) {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        MIGRATOR.run(&mut conn).await.unwrap();
+        for (evt_type, timestamp, message) in [
+            (EvtType::Start, "2024-05-02T11:00:00Z", "#1"),
+            (EvtType::Stop, "2024-05-02T12:30:00Z", ""),
+        ] {
+            InsertEvent {
+                evt_type,
+                timestamp: timestamp.parse().unwrap(),
+                message: message.into(),
+                provisional: false,
+            }
+            .insert(&mut conn, Overlap::Refuse)
+            .await
+            .unwrap();
+        }
+        app(conn.into(), Options::default(), false)
+    }
+
+    /// Make each request in turn, returning the status and JSON body of each response.
+    async fn requests(requests: Vec<Request<Body>>) -> Vec<(StatusCode, Value)> {
+        let (router, worker) = fixture().await;
+        let local = tokio::task::LocalSet::new();
+        local.spawn_local(worker);
+        local
+            .run_until(async {
+                let mut responses = Vec::new();
+                for request in requests {
+                    let response = router.clone().oneshot(request).await.unwrap();
+                    let status = response.status();
+                    let body = response.into_body().collect().await.unwrap().to_bytes();
+                    let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+                    responses.push((status, body));
+                }
+                responses
+            })
+            .await
+    }
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    fn with_json(method: &str, uri: &str, body: Value) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn list_events() {
+        let [(status, body)] = requests(vec![get("/events?from=2024-05-02")])
+            .await
+            .try_into()
+            .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!([
+                {"id": 1, "evt_type": "START", "timestamp": "2024-05-02T11:00:00Z", "message": "#1", "provisional": false},
+                {"id": 2, "evt_type": "STOP", "timestamp": "2024-05-02T12:30:00Z", "message": "", "provisional": false},
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn report() {
+        let [(status, body)] = requests(vec![get("/report?from=2024-05-01&to=2024-05-03")])
+            .await
+            .try_into()
+            .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total_seconds"], 5400);
+        assert_eq!(body["tasks"][0]["message"], "#1");
+    }
+
+    #[tokio::test]
+    async fn status_idle() {
+        let [(status, body)] = requests(vec![get("/status")]).await.try_into().unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"task": null}));
+    }
+
+    #[tokio::test]
+    async fn insert_then_update_then_delete() {
+        let responses = requests(vec![
+            with_json(
+                "POST",
+                "/events",
+                json!({"evt_type": "START", "timestamp": "2024-05-02T13:00:00Z", "message": "#2"}),
+            ),
+            with_json("PATCH", "/events/3", json!({"message": "#3"})),
+            Request::delete("/events/3").body(Body::empty()).unwrap(),
+            get("/events/3"),
+        ])
+        .await;
+        assert_eq!(responses[0].0, StatusCode::CREATED);
+        assert_eq!(responses[0].1["id"], 3);
+        assert_eq!(responses[1].0, StatusCode::OK);
+        assert_eq!(responses[1].1["message"], "#3");
+        assert_eq!(responses[2].0, StatusCode::NO_CONTENT);
+        assert_eq!(responses[3].0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn insert_reshaping_history() {
+        let event =
+            json!({"evt_type": "START", "timestamp": "2024-05-02T12:00:00Z", "message": "#2"});
+        let responses = requests(vec![
+            with_json("POST", "/events", event.clone()),
+            with_json("POST", "/events?force=true", event),
+        ])
+        .await;
+        assert_eq!(responses[0].0, StatusCode::CONFLICT);
+        assert_eq!(responses[0].1["reshapes"], json!([1]));
+        assert_eq!(responses[1].0, StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn update_reordering_events() {
+        let responses = requests(vec![
+            with_json(
+                "PATCH",
+                "/events/1",
+                json!({"timestamp": "2024-05-02T13:00:00Z"}),
+            ),
+            with_json(
+                "PATCH",
+                "/events/1",
+                json!({"timestamp": "2024-05-02T10:00:00Z"}),
+            ),
+            with_json(
+                "PATCH",
+                "/events/2",
+                json!({"timestamp": "2024-05-02T09:30:00Z"}),
+            ),
+            with_json(
+                "PATCH",
+                "/events/2?force=true",
+                json!({"timestamp": "2024-05-02T09:30:00Z"}),
+            ),
+        ])
+        .await;
+        assert_eq!(responses[0].0, StatusCode::CONFLICT);
+        assert_eq!(responses[1].0, StatusCode::OK);
+        assert_eq!(responses[1].1["timestamp"], "2024-05-02T10:00:00Z");
+        assert_eq!(responses[2].0, StatusCode::CONFLICT);
+        assert_eq!(responses[3].0, StatusCode::OK);
+        assert_eq!(responses[3].1["timestamp"], "2024-05-02T09:30:00Z");
+    }
+}
@@ -0,0 +1,164 @@
+//! A small file caching the task in progress, so that frequent readers like shell prompts needn't open the database.
+//!
+//! Every action which changes the database rewrites it. It's only trusted while it's newer than the database.
+
+use std::{path::PathBuf, time::SystemTime};
+
+use chrono::{DateTime, Duration, Local, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+
+use crate::{
+    db::{self, EvtType, Id, RetrieveEvent},
+    paths,
+};
+
+/// The task in progress when the state was written.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CurrentTask {
+    /// The id of the START event which began this task.
+    pub id: Id,
+    pub message: String,
+    pub start: DateTime<Utc>,
+    /// When the task is scheduled to stop, if it was timeboxed.
+    pub scheduled_stop: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct State {
+    pub task: Option<CurrentTask>,
+}
+
+impl State {
+    /// Read the current state from the database.
+    pub async fn query(conn: &mut SqliteConnection) -> Result<Self, Error> {
+        let now = Utc::now();
+        let Some(start) = RetrieveEvent::last_before(conn, now)
+            .await?
+            .filter(|event| matches!(event.evt_type, EvtType::Start))
+        else {
+            return Ok(Self::default());
+        };
+        let scheduled_stop = RetrieveEvent::first_after(conn, now)
+            .await?
+            .filter(|event| event.provisional && matches!(event.evt_type, EvtType::Stop))
+            .map(|event| event.timestamp);
+
+        Ok(Self {
+            task: Some(CurrentTask {
+                id: start.id,
+                message: start.message,
+                start: start.timestamp,
+                scheduled_stop,
+            }),
+        })
+    }
+
+    /// Read the current state from the database, and write it to the state file.
+    pub async fn refresh(conn: &mut SqliteConnection) -> Result<Self, Error> {
+        let state = Self::query(conn).await?;
+        state.write()?;
+        Ok(state)
+    }
+
+    fn write(&self) -> Result<(), Error> {
+        let path = paths::state();
+        let json = serde_json::to_vec(self).map_err(Error::Serialize)?;
+        // write then rename, so that readers never see a partial file
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).map_err(|err| Error::Write(tmp.clone(), err))?;
+        std::fs::rename(&tmp, &path).map_err(|err| Error::Write(path, err))
+    }
+
+    /// Read the state file, if it's at least as new as the database.
+    ///
+    /// Returns `None` if the file is missing, unreadable, or stale; the database is the source of truth.
+    pub fn load_fresh() -> Option<Self> {
+        let modified =
+            |path: PathBuf| -> Option<SystemTime> { path.metadata().ok()?.modified().ok() };
+        if modified(paths::state())? < modified(paths::database())? {
+            return None;
+        }
+        let contents = std::fs::read(paths::state()).ok()?;
+        serde_json::from_slice(&contents).ok()
+    }
+
+    /// The task in progress at `now`.
+    ///
+    /// A timeboxed task stops at its scheduled time even if nothing has been written since.
+    pub fn task_at(&self, now: DateTime<Utc>) -> Option<&CurrentTask> {
+        self.task
+            .as_ref()
+            .filter(|task| task.scheduled_stop.is_none_or(|stop| now < stop))
+    }
+
+    /// Render this state through a format string.
+    ///
+    /// These placeholders are replaced: `{id}`, `{message}`, `{start}`, `{elapsed}`, and `{remaining}`.
+    /// If nothing is in progress, `idle` is rendered instead.
+    pub fn render(&self, format: &str, idle: &str, now: DateTime<Utc>) -> String {
+        let Some(task) = self.task_at(now) else {
+            return idle.to_owned();
+        };
+        let hours_minutes = |duration: Duration| {
+            let minutes = duration.num_minutes();
+            format!("{}:{:02}", minutes / 60, minutes % 60)
+        };
+        let start: DateTime<Local> = task.start.into();
+        let remaining = task
+            .scheduled_stop
+            .map(|stop| hours_minutes(stop - now))
+            .unwrap_or_default();
+        format
+            .replace("{id}", &task.id.to_string())
+            .replace("{start}", &start.format("%H:%M").to_string())
+            .replace("{elapsed}", &hours_minutes(now - task.start))
+            .replace("{remaining}", &remaining)
+            // last, so that placeholder-like text in the message is left alone
+            .replace("{message}", &task.message)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("serializing state")]
+    Serialize(#[source] serde_json::Error),
+    #[error("writing state file {0}")]
+    Write(PathBuf, #[source] std::io::Error),
+    #[error(transparent)]
+    Db(#[from] db::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone as _;
+
+    use super::*;
+
+    fn state() -> State {
+        let start = Utc.with_ymd_and_hms(2024, 5, 2, 9, 0, 0).unwrap();
+        State {
+            task: Some(CurrentTask {
+                id: 12,
+                message: "fix {elapsed} parsing".into(),
+                start,
+                scheduled_stop: Some(start + Duration::minutes(30)),
+            }),
+        }
+    }
+
+    #[test]
+    fn render_running_task() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 2, 9, 10, 0).unwrap();
+        assert_eq!(
+            state().render("#{id} {message} {elapsed}/{remaining}", "idle", now),
+            "#12 fix {elapsed} parsing 0:10/0:20"
+        );
+    }
+
+    #[test]
+    fn scheduled_stop_ends_task() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 2, 9, 30, 0).unwrap();
+        assert_eq!(state().render("{message}", "idle", now), "idle");
+    }
+}
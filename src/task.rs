@@ -0,0 +1,244 @@
+//! Tasks are reconstructed from the START and STOP events which bound them.
+
+use std::fmt;
+
+use chrono::{DateTime, Duration, Local, TimeZone as _, Utc};
+use serde::Serialize;
+
+use crate::{
+    config::Workday,
+    db::{EvtType, Id, RetrieveEvent},
+};
+
+#[derive(Debug, Serialize)]
+pub struct Task {
+    pub start: DateTime<Local>,
+    pub stop: Option<DateTime<Local>>,
+    /// The task was ended by a STOP, rather than by the next START or not at all.
+    pub stopped: bool,
+    /// The id of the START event which began this task.
+    pub id: Id,
+    pub message: String,
+    /// The stop was inferred from the workday limits rather than logged.
+    pub implicit_stop: bool,
+    /// The task was worked as a pomodoro, which ran to completion.
+    pub pomodoro: bool,
+}
+
+impl Task {
+    pub fn duration(&self) -> Option<Duration> {
+        self.stop.map(|stop| stop - self.start)
+    }
+
+    /// Treat this task as stopped if it ran past the limits of the workday.
+    ///
+    /// A task is limited by the end of the workday on the day it started, if it started before then,
+    /// and by the maximum task length. Tasks which were explicitly stopped are left alone.
+    /// Return whether the stop was changed.
+    pub fn apply_limits(&mut self, workday: &Workday, now: DateTime<Local>) -> bool {
+        if self.stopped {
+            return false;
+        }
+        let end_of_day = workday
+            .end
+            .and_then(|end| {
+                Local
+                    .from_local_datetime(&self.start.date_naive().and_time(end))
+                    .earliest()
+            })
+            .filter(|end_of_day| *end_of_day > self.start);
+        let max_length = workday.max_task_length.map(|max| self.start + max);
+
+        let Some(limit) = end_of_day.into_iter().chain(max_length).min() else {
+            return false;
+        };
+        if self.stop.unwrap_or(now) > limit {
+            self.stop = Some(limit);
+            self.implicit_stop = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Reconstruct the list of tasks from a list of events ordered by timestamp.
+    ///
+    /// Each START begins a task, which is ended by whatever event comes next.
+    /// The final task may still be in progress.
+    pub fn from_events(events: impl IntoIterator<Item = RetrieveEvent>) -> Vec<Self> {
+        let mut tasks = Vec::new();
+
+        // provisional events which haven't happened yet shouldn't end any tasks
+        let now = Utc::now();
+        let events = events
+            .into_iter()
+            .filter(|event| !(event.provisional && event.timestamp > now));
+
+        let mut in_progress: Option<Task> = None;
+        for event in events {
+            if let Some(mut in_progress) = in_progress.take() {
+                in_progress.stop = Some(event.timestamp.into());
+                in_progress.stopped = matches!(event.evt_type, EvtType::Stop);
+                tasks.push(in_progress);
+            }
+            if let EvtType::Start = event.evt_type {
+                in_progress = Some(Task {
+                    start: event.timestamp.into(),
+                    stop: None,
+                    stopped: false,
+                    id: event.id,
+                    message: event.message,
+                    implicit_stop: false,
+                    pomodoro: false,
+                });
+            }
+        }
+        // we might have a final event in progress
+        if let Some(in_progress) = in_progress {
+            tasks.push(in_progress);
+        }
+
+        tasks
+    }
+}
+
+#[cfg(test)]
+impl Task {
+    /// A task which was logged as stopping at `stop`.
+    pub(crate) fn closed(
+        id: Id,
+        start: DateTime<Local>,
+        stop: DateTime<Local>,
+        message: &str,
+    ) -> Self {
+        Self {
+            stop: Some(stop),
+            stopped: true,
+            ..Self::running(id, start, message)
+        }
+    }
+
+    /// A task which is still in progress.
+    pub(crate) fn running(id: Id, start: DateTime<Local>, message: &str) -> Self {
+        Self {
+            start,
+            stop: None,
+            stopped: false,
+            id,
+            message: message.into(),
+            implicit_stop: false,
+            pomodoro: false,
+        }
+    }
+}
+
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let start = self.start.format("%H%M");
+        let stop = self
+            .stop
+            .map(|stop| stop.format("%H%M").to_string())
+            .unwrap_or(String::from("…   "));
+        let marker = if self.implicit_stop { "*" } else { "" };
+        let pomodoro = if self.pomodoro { " 🍅" } else { "" };
+        let duration = self.duration().unwrap_or(Duration::zero());
+        let minutes = duration.num_minutes();
+        let hours = minutes / 60;
+        let minutes = minutes % 60;
+        let id = self.id;
+        let message = &self.message;
+
+        write!(
+            f,
+            "[{start}–{stop}{marker}] ({hours}:{minutes:02}{pomodoro}) #{id}: {message}"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Local> {
+        Local
+            .from_local_datetime(&format!("2024-05-02T{time}:00").parse().unwrap())
+            .unwrap()
+    }
+
+    fn event(id: Id, evt_type: EvtType, time: &str) -> RetrieveEvent {
+        RetrieveEvent {
+            id,
+            evt_type,
+            timestamp: at(time).into(),
+            message: format!("#{id}"),
+            provisional: false,
+        }
+    }
+
+    fn workday() -> Workday {
+        Workday {
+            end: NaiveTime::from_hms_opt(18, 0, 0),
+            ..Workday::default()
+        }
+    }
+
+    #[test]
+    fn from_events_notes_stops() {
+        let tasks = Task::from_events([
+            event(1, EvtType::Start, "09:00"),
+            event(2, EvtType::Start, "10:00"),
+            event(3, EvtType::Stop, "11:00"),
+        ]);
+        assert!(!tasks[0].stopped);
+        assert!(tasks[1].stopped);
+    }
+
+    #[test]
+    fn explicit_stop_past_end_of_day_is_kept() {
+        let mut tasks = Task::from_events([
+            event(1, EvtType::Start, "17:00"),
+            event(2, EvtType::Stop, "19:30"),
+        ]);
+        assert!(!tasks[0].apply_limits(&workday(), at("20:00")));
+        assert_eq!(tasks[0].stop, Some(at("19:30")));
+        assert!(!tasks[0].implicit_stop);
+    }
+
+    #[test]
+    fn next_start_past_end_of_day_is_limited() {
+        let mut tasks = Task::from_events([
+            event(1, EvtType::Start, "17:00"),
+            event(2, EvtType::Start, "19:30"),
+        ]);
+        assert!(tasks[0].apply_limits(&workday(), at("20:00")));
+        assert_eq!(tasks[0].stop, Some(at("18:00")));
+        assert!(tasks[0].implicit_stop);
+    }
+
+    #[test]
+    fn running_task_is_limited() {
+        let mut tasks = Task::from_events([event(1, EvtType::Start, "17:00")]);
+        assert!(!tasks[0].apply_limits(&workday(), at("17:30")));
+        assert_eq!(tasks[0].stop, None);
+
+        assert!(tasks[0].apply_limits(&workday(), at("18:30")));
+        assert_eq!(tasks[0].stop, Some(at("18:00")));
+    }
+
+    #[test]
+    fn max_task_length() {
+        let workday = Workday {
+            max_task_length: Some(Duration::hours(2)),
+            ..Workday::default()
+        };
+        let mut tasks = Task::from_events([
+            event(1, EvtType::Start, "09:00"),
+            event(2, EvtType::Start, "13:00"),
+        ]);
+        assert!(tasks[0].apply_limits(&workday, at("14:00")));
+        assert_eq!(tasks[0].stop, Some(at("11:00")));
+        assert!(!tasks[1].apply_limits(&workday, at("14:00")));
+    }
+}
@@ -0,0 +1,595 @@
+//! An interactive timeline of tasks, for reviewing and correcting them.
+//!
+//! Every change goes through [`edit`], so each one can be undone for as long as the timeline is open.
+
+use std::time::{Duration as StdDuration, Instant};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, TimeZone as _, Utc};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Style, Stylize as _},
+    text::Line,
+    widgets::{List, ListItem, ListState, Paragraph},
+    DefaultTerminal, Frame,
+};
+use sqlx::SqliteConnection;
+
+use crate::{
+    action::{self, total_duration, Options, Period},
+    db::Id,
+    edit::{self, Revision},
+    state::{self, State},
+    task::Task,
+};
+
+/// How often the running timer is redrawn.
+const TICK: StdDuration = StdDuration::from_secs(1);
+/// How often the tasks are reloaded, in case something else changed them.
+const RELOAD: StdDuration = StdDuration::from_secs(10);
+
+const HELP: &str = "↑↓ select  ←→ day  w week  t today  e message  b start  f stop  s split  m merge  d delete  u undo  q quit";
+
+/// Show the timeline until the user quits.
+pub async fn run(conn: &mut SqliteConnection, options: &Options) -> Result<(), Error> {
+    let mut app = App::new(Local::now().date_naive());
+    app.reload(conn, options).await?;
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal, conn, options).await;
+    ratatui::restore();
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    Day,
+    Week,
+}
+
+/// What the user is typing into the footer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+    Message,
+    Start,
+    Stop,
+    Split,
+    /// Merge with the task which follows.
+    Merge {
+        next: Id,
+    },
+}
+
+impl Prompt {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Message => "message",
+            Self::Start => "start",
+            Self::Stop => "stop",
+            Self::Split => "split at (hhmm[: message])",
+            Self::Merge { .. } => "merged message",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    Prompt {
+        prompt: Prompt,
+        /// The task being edited.
+        task: Id,
+        /// The day on which times are entered.
+        date: NaiveDate,
+        input: String,
+    },
+}
+
+/// A change requested by the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Change {
+    Rename(Id, String),
+    MoveStart(Id, DateTime<Utc>),
+    MoveStop(Id, DateTime<Utc>),
+    Split(Id, DateTime<Utc>, Option<String>),
+    Merge(Id, Id, Option<String>),
+    Delete(Id),
+    Undo,
+}
+
+struct App {
+    view: View,
+    date: NaiveDate,
+    tasks: Vec<Task>,
+    list: ListState,
+    mode: Mode,
+    /// Changes made while the timeline has been open, most recent last.
+    revisions: Vec<Revision>,
+    status: Option<String>,
+    /// Whether the tasks must be reloaded because the period changed.
+    stale: bool,
+    quit: bool,
+}
+
+impl App {
+    fn new(date: NaiveDate) -> Self {
+        Self {
+            view: View::Day,
+            date,
+            tasks: Vec::new(),
+            list: ListState::default(),
+            mode: Mode::Browse,
+            revisions: Vec::new(),
+            status: None,
+            stale: false,
+            quit: false,
+        }
+    }
+
+    fn period(&self) -> Period {
+        match self.view {
+            View::Day => Period::day(self.date),
+            View::Week => Period::week_of(self.date),
+        }
+    }
+
+    async fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        conn: &mut SqliteConnection,
+        options: &Options,
+    ) -> Result<(), Error> {
+        let mut loaded = Instant::now();
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if event::poll(TICK)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        if let Some(change) = self.handle_key(key) {
+                            self.apply(conn, change).await?;
+                            self.stale = true;
+                        }
+                    }
+                }
+            }
+
+            if self.stale || loaded.elapsed() >= RELOAD {
+                self.reload(conn, options).await?;
+                loaded = Instant::now();
+            }
+        }
+        Ok(())
+    }
+
+    async fn reload(
+        &mut self,
+        conn: &mut SqliteConnection,
+        options: &Options,
+    ) -> Result<(), Error> {
+        let (tasks, _) = action::tasks_in(conn, options, self.period())
+            .await
+            .map_err(|err| Error::Tasks(Box::new(err)))?;
+        self.tasks = tasks;
+        self.stale = false;
+
+        let selected = match self.list.selected() {
+            _ if self.tasks.is_empty() => None,
+            Some(selected) => Some(selected.min(self.tasks.len() - 1)),
+            None => Some(self.tasks.len() - 1),
+        };
+        self.list.select(selected);
+        Ok(())
+    }
+
+    fn selected(&self) -> Option<&Task> {
+        self.list.selected().and_then(|index| self.tasks.get(index))
+    }
+
+    /// Update the view for a key press, returning the change it requests, if any.
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Change> {
+        if let Mode::Prompt { input, .. } = &mut self.mode {
+            match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Enter => {
+                    let Mode::Prompt {
+                        prompt,
+                        task,
+                        date,
+                        input,
+                    } = std::mem::replace(&mut self.mode, Mode::Browse)
+                    else {
+                        unreachable!("we're in a prompt");
+                    };
+                    return self.submit(prompt, task, date, input);
+                }
+                _ => {}
+            }
+            return None;
+        }
+
+        self.status = None;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('j') | KeyCode::Down => self.list.select_next(),
+            KeyCode::Char('k') | KeyCode::Up => self.list.select_previous(),
+            KeyCode::Char('g') | KeyCode::Home => self.list.select_first(),
+            KeyCode::Char('G') | KeyCode::End => self.list.select_last(),
+            KeyCode::Char('h') | KeyCode::Left => self.shift(-1),
+            KeyCode::Char('l') | KeyCode::Right => self.shift(1),
+            KeyCode::Char('w') => {
+                self.view = match self.view {
+                    View::Day => View::Week,
+                    View::Week => View::Day,
+                };
+                self.stale = true;
+            }
+            KeyCode::Char('t') => {
+                self.date = Local::now().date_naive();
+                self.stale = true;
+            }
+            KeyCode::Char('e') => self.prompt(Prompt::Message),
+            KeyCode::Char('b') => self.prompt(Prompt::Start),
+            KeyCode::Char('f') => self.prompt(Prompt::Stop),
+            KeyCode::Char('s') => self.prompt(Prompt::Split),
+            KeyCode::Char('m') => {
+                let next = self
+                    .list
+                    .selected()
+                    .and_then(|index| self.tasks.get(index + 1));
+                match next {
+                    Some(next) => self.prompt(Prompt::Merge { next: next.id }),
+                    None => self.status = Some("no following task to merge with".into()),
+                }
+            }
+            KeyCode::Char('d') => return self.selected().map(|task| Change::Delete(task.id)),
+            KeyCode::Char('u') => return Some(Change::Undo),
+            _ => {}
+        }
+        None
+    }
+
+    /// Move the view by a number of days, or weeks in the week view.
+    fn shift(&mut self, by: i64) {
+        let days = match self.view {
+            View::Day => by,
+            View::Week => by * 7,
+        };
+        self.date += Duration::days(days);
+        self.list.select(None);
+        self.stale = true;
+    }
+
+    /// Start prompting for input about the selected task, prefilled with what it currently holds.
+    fn prompt(&mut self, prompt: Prompt) {
+        let Some(task) = self.selected() else {
+            return;
+        };
+        let (input, date) = match prompt {
+            Prompt::Message | Prompt::Merge { .. } => (task.message.clone(), task.start),
+            Prompt::Start => (task.start.format("%H%M").to_string(), task.start),
+            Prompt::Stop => match task.stop {
+                Some(stop) => (stop.format("%H%M").to_string(), stop),
+                None => {
+                    self.status = Some(format!("#{} hasn't stopped yet", task.id));
+                    return;
+                }
+            },
+            Prompt::Split => (String::new(), task.start),
+        };
+        self.mode = Mode::Prompt {
+            prompt,
+            task: task.id,
+            date: date.date_naive(),
+            input,
+        };
+    }
+
+    fn submit(
+        &mut self,
+        prompt: Prompt,
+        task: Id,
+        date: NaiveDate,
+        input: String,
+    ) -> Option<Change> {
+        let input = input.trim();
+        let mut time = |input: &str| {
+            let time = parse_time(date, input);
+            if time.is_none() {
+                self.status = Some(format!("invalid time: \"{input}\""));
+            }
+            time
+        };
+        match prompt {
+            Prompt::Message => Some(Change::Rename(task, input.to_owned())),
+            Prompt::Start => time(input).map(|time| Change::MoveStart(task, time)),
+            Prompt::Stop => time(input).map(|time| Change::MoveStop(task, time)),
+            Prompt::Split => {
+                let (at, message) = split_input(input);
+                time(at).map(|at| Change::Split(task, at, message))
+            }
+            Prompt::Merge { next } => {
+                let message = (!input.is_empty()).then(|| input.to_owned());
+                Some(Change::Merge(task, next, message))
+            }
+        }
+    }
+
+    async fn apply(&mut self, conn: &mut SqliteConnection, change: Change) -> Result<(), Error> {
+        let (result, done) = match change {
+            Change::Undo => {
+                let Some(revision) = self.revisions.pop() else {
+                    self.status = Some("nothing to undo".into());
+                    return Ok(());
+                };
+                (revision.revert(conn).await.map(|_| None), "undone".into())
+            }
+            Change::Rename(id, message) => (
+                edit::rename(conn, id, message).await.map(Some),
+                format!("renamed #{id}"),
+            ),
+            Change::MoveStart(id, to) => (
+                edit::move_event(conn, id, to).await.map(Some),
+                format!("moved the start of #{id}"),
+            ),
+            Change::MoveStop(id, to) => (
+                edit::move_stop(conn, id, to).await.map(Some),
+                format!("moved the stop of #{id}"),
+            ),
+            Change::Split(id, at, message) => (
+                edit::split_task(conn, id, at, message).await.map(Some),
+                format!("split #{id}"),
+            ),
+            Change::Merge(first, second, message) => (
+                edit::merge_tasks(conn, first, second, message)
+                    .await
+                    .map(Some),
+                format!("merged #{second} into #{first}"),
+            ),
+            Change::Delete(id) => (
+                edit::delete_task(conn, id).await.map(Some),
+                format!("deleted #{id} (u to restore)"),
+            ),
+        };
+        match result {
+            Ok(revision) => {
+                self.revisions.extend(revision);
+                self.status = Some(done);
+                // keep shell prompts in step with the timeline
+                State::refresh(conn).await?;
+            }
+            Err(err) => self.status = Some(err.to_string()),
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let now = Local::now();
+
+        let period = match self.view {
+            View::Day => self.date.format("%A %Y-%m-%d").to_string(),
+            View::Week => format!("week of {}", self.period().first.format("%Y-%m-%d")),
+        };
+        let total = total_duration(&self.tasks) + running_time(&self.tasks, now);
+        let title = format!("{period}  total {}", hours_minutes(total));
+        frame.render_widget(Paragraph::new(Line::from(title).bold()), header);
+
+        let show_date = self.view == View::Week;
+        let items = self
+            .tasks
+            .iter()
+            .map(|task| ListItem::new(row(task, now, show_date)));
+        let list = List::new(items)
+            .highlight_style(Style::new().reversed())
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(list, body, &mut self.list);
+
+        let footer_text = match &self.mode {
+            Mode::Prompt { prompt, input, .. } => format!("{}: {input}█", prompt.label()),
+            Mode::Browse => self.status.clone().unwrap_or_else(|| HELP.into()),
+        };
+        frame.render_widget(Paragraph::new(footer_text), footer);
+    }
+}
+
+/// How long the task in progress has run so far.
+fn running_time(tasks: &[Task], now: DateTime<Local>) -> Duration {
+    tasks
+        .iter()
+        .filter(|task| task.stop.is_none())
+        .map(|task| now - task.start)
+        .fold(Duration::zero(), |total, item| total + item)
+}
+
+fn hours_minutes(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// A line of the timeline. The task in progress shows its running time to the second.
+fn row(task: &Task, now: DateTime<Local>, show_date: bool) -> String {
+    let date = if show_date {
+        task.start.format("%a %m-%d ").to_string()
+    } else {
+        String::new()
+    };
+    let start = task.start.format("%H%M");
+    let marker = if task.implicit_stop { "*" } else { "" };
+    let pomodoro = if task.pomodoro { " 🍅" } else { "" };
+    let (stop, duration) = match task.stop {
+        Some(stop) => (
+            stop.format("%H%M").to_string(),
+            hours_minutes(stop - task.start),
+        ),
+        None => {
+            let seconds = (now - task.start).num_seconds();
+            let duration = format!(
+                "{}:{:02}:{:02}",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            );
+            ("…   ".into(), duration)
+        }
+    };
+    format!(
+        "{date}[{start}–{stop}{marker}] ({duration}{pomodoro}) #{}: {}",
+        task.id, task.message
+    )
+}
+
+/// Parse a time of day entered on `date`, like `1415` or `14:15`.
+fn parse_time(date: NaiveDate, input: &str) -> Option<DateTime<Utc>> {
+    let time = ["%H%M", "%H:%M"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(input, format).ok())?;
+    Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .map(Into::into)
+}
+
+/// Split input like `1415: new message` into its time and optional message.
+fn split_input(input: &str) -> (&str, Option<String>) {
+    let end = input
+        .find(|c: char| !(c.is_ascii_digit() || c == ':'))
+        .unwrap_or(input.len());
+    let (time, message) = input.split_at(end);
+    let message = message.trim();
+    (
+        time.trim_end_matches(':'),
+        (!message.is_empty()).then(|| message.to_owned()),
+    )
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("drawing the timeline")]
+    Terminal(#[from] std::io::Error),
+    #[error("listing tasks")]
+    Tasks(#[source] Box<action::Error>),
+    #[error("caching the task in progress")]
+    State(#[from] state::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::from(code)
+    }
+
+    fn type_text(app: &mut App, text: &str) -> Option<Change> {
+        for c in text.chars() {
+            app.handle_key(key(KeyCode::Char(c)));
+        }
+        app.handle_key(key(KeyCode::Enter))
+    }
+
+    fn task(id: Id, start: &str, stop: Option<&str>, message: &str) -> Task {
+        let at = |time: &str| {
+            Local
+                .from_local_datetime(&format!("2024-05-02T{time}:00").parse().unwrap())
+                .unwrap()
+        };
+        match stop {
+            Some(stop) => Task::closed(id, at(start), at(stop), message),
+            None => Task::running(id, at(start), message),
+        }
+    }
+
+    fn app() -> App {
+        let mut app = App::new("2024-05-02".parse().unwrap());
+        app.tasks = vec![
+            task(1, "09:00", Some("10:00"), "a"),
+            task(3, "10:15", None, "b"),
+        ];
+        app.list.select(Some(0));
+        app
+    }
+
+    #[test]
+    fn split_input_forms() {
+        assert_eq!(split_input("1415"), ("1415", None));
+        assert_eq!(split_input("1415: review"), ("1415", Some("review".into())));
+        assert_eq!(
+            split_input("14:15: review"),
+            ("14:15", Some("review".into()))
+        );
+    }
+
+    #[test]
+    fn edit_message() {
+        let mut app = app();
+        app.handle_key(key(KeyCode::Char('e')));
+        app.handle_key(key(KeyCode::Backspace));
+        assert_eq!(
+            type_text(&mut app, "standup"),
+            Some(Change::Rename(1, "standup".into()))
+        );
+        assert_eq!(app.mode, Mode::Browse);
+    }
+
+    #[test]
+    fn split_at_time() {
+        let mut app = app();
+        app.handle_key(key(KeyCode::Char('s')));
+        let at = parse_time(app.date, "0930").unwrap();
+        assert_eq!(
+            type_text(&mut app, "0930: review"),
+            Some(Change::Split(1, at, Some("review".into())))
+        );
+
+        app.handle_key(key(KeyCode::Char('s')));
+        assert_eq!(type_text(&mut app, "2500: later"), None);
+        assert_eq!(app.status.as_deref(), Some("invalid time: \"2500\""));
+    }
+
+    #[test]
+    fn merge_with_next() {
+        let mut app = app();
+        app.handle_key(key(KeyCode::Char('m')));
+        assert_eq!(
+            type_text(&mut app, " and b"),
+            Some(Change::Merge(1, 3, Some("a and b".into())))
+        );
+
+        app.handle_key(key(KeyCode::Down));
+        app.handle_key(key(KeyCode::Char('m')));
+        assert_eq!(app.mode, Mode::Browse);
+    }
+
+    #[test]
+    fn running_task_has_no_stop_to_edit() {
+        let mut app = app();
+        app.handle_key(key(KeyCode::Down));
+        app.handle_key(key(KeyCode::Char('f')));
+        assert_eq!(app.mode, Mode::Browse);
+        assert_eq!(app.status.as_deref(), Some("#3 hasn't stopped yet"));
+    }
+
+    #[test]
+    fn running_timer() {
+        let app = app();
+        let now = app.tasks[1].start + Duration::seconds(3723);
+        assert_eq!(
+            row(&app.tasks[1], now, false),
+            "[1015–…   ] (1:02:03) #3: b"
+        );
+        assert_eq!(
+            row(&app.tasks[0], now, true),
+            "Thu 05-02 [0900–1000] (1:00) #1: a"
+        );
+    }
+}